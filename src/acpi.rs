@@ -0,0 +1,353 @@
+//! Discovery of the ACPI tables the firmware leaves in memory.
+//!
+//! `init` finds and checks them once; the other subsystems then query the parsed
+//! tables through `madt`, `fadt` and `hpet`.
+
+use crate::memory;
+use alloc::vec::Vec;
+use conquer_once::spin::OnceCell;
+use core::{mem, ptr, slice, str};
+use x86_64::{
+    structures::paging::{Page, Size4KiB},
+    PhysAddr,
+};
+
+pub use fadt::{AddressSpace, Fadt, GenericAddress};
+pub use hpet::Hpet;
+pub use madt::{isa_irq, InterruptOverride, IoApic, Madt, Polarity, ProcessorApic, TriggerMode};
+
+mod fadt;
+mod hpet;
+mod madt;
+
+const RSDP_SIGNATURE: &[u8; 8] = b"RSD PTR ";
+/// Length of the RSDP in ACPI 1.0, which the first checksum covers.
+const RSDP_V1_LENGTH: usize = 20;
+/// Longer tables are taken for corrupt rather than read.
+const MAX_TABLE_LENGTH: usize = 1 << 20;
+
+static TABLES: OnceCell<AcpiTables> = OnceCell::uninit();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcpiError {
+    /// `memory::init` has not run, or the physical memory mapping doesn't cover the
+    /// table.
+    MemoryNotMapped,
+    /// `init` has not run or failed.
+    NotInitialized,
+    AlreadyInitialized,
+    RsdpNotFound,
+    BadChecksum([u8; 4]),
+    TableNotFound([u8; 4]),
+    /// The table is shorter than its fixed fields.
+    TableTooShort([u8; 4]),
+    /// The table claims to be longer than `MAX_TABLE_LENGTH`.
+    TableTooLong([u8; 4]),
+}
+
+/// Everything `init` found.
+pub struct AcpiTables {
+    /// 0 for ACPI 1.0, 2 from ACPI 2.0 on.
+    pub revision: u8,
+    pub oem_id: [u8; 6],
+    /// Every table the RSDT or XSDT points to, in order.
+    pub tables: Vec<Table>,
+    pub madt: Option<Madt>,
+    pub fadt: Option<Fadt>,
+    pub hpet: Option<Hpet>,
+}
+
+/// The header every system description table starts with.
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+pub struct SdtHeader {
+    pub signature: [u8; 4],
+    pub length: u32,
+    pub revision: u8,
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+    pub oem_table_id: [u8; 8],
+    pub oem_revision: u32,
+    pub creator_id: u32,
+    pub creator_revision: u32,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C, packed)]
+struct Rsdp {
+    signature: [u8; 8],
+    checksum: u8,
+    oem_id: [u8; 6],
+    revision: u8,
+    rsdt_address: u32,
+    // the rest is only there from revision 2 on
+    length: u32,
+    xsdt_address: u64,
+    extended_checksum: u8,
+    reserved: [u8; 3],
+}
+
+/// A system description table whose checksum has been verified.
+#[derive(Clone, Copy)]
+pub struct Table {
+    address: PhysAddr,
+    header: SdtHeader,
+    bytes: &'static [u8],
+}
+
+impl Table {
+    pub fn at(address: PhysAddr) -> Result<Table, AcpiError> {
+        let header_bytes = physical_bytes(address, mem::size_of::<SdtHeader>())?;
+        let header: SdtHeader = unsafe { ptr::read_unaligned(header_bytes.as_ptr().cast()) };
+        let length = header.length as usize;
+        if length > MAX_TABLE_LENGTH {
+            return Err(AcpiError::TableTooLong(header.signature));
+        }
+        let length = length.max(mem::size_of::<SdtHeader>());
+        let bytes = physical_bytes(address, length)?;
+        if !has_valid_checksum(bytes) {
+            return Err(AcpiError::BadChecksum(header.signature));
+        }
+        Ok(Table {
+            address,
+            header,
+            bytes,
+        })
+    }
+
+    pub fn address(&self) -> PhysAddr {
+        self.address
+    }
+
+    pub fn header(&self) -> &SdtHeader {
+        &self.header
+    }
+
+    pub fn signature(&self) -> [u8; 4] {
+        self.header.signature
+    }
+
+    /// The contents of the table after its header.
+    pub fn data(&self) -> &'static [u8] {
+        &self.bytes[mem::size_of::<SdtHeader>()..]
+    }
+
+    /// The whole table, for the fields whose offsets the specification gives from its start.
+    pub fn bytes(&self) -> &'static [u8] {
+        self.bytes
+    }
+}
+
+/// Returns `len` bytes of physical memory from the complete physical memory mapping,
+/// once every page of them is known to be mapped.
+fn physical_bytes(address: PhysAddr, len: usize) -> Result<&'static [u8], AcpiError> {
+    let start = memory::phys_to_virt(address).ok_or(AcpiError::MemoryNotMapped)?;
+    let end = start + (len.max(1) - 1);
+    let mut pages = Page::<Size4KiB>::range_inclusive(
+        Page::containing_address(start),
+        Page::containing_address(end),
+    );
+    if pages.any(|page| memory::page_flags(page.start_address()).is_none()) {
+        return Err(AcpiError::MemoryNotMapped);
+    }
+    Ok(unsafe { slice::from_raw_parts(start.as_ptr(), len) })
+}
+
+fn has_valid_checksum(bytes: &[u8]) -> bool {
+    bytes.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)) == 0
+}
+
+/// Searches the first KiB of the extended BIOS data area and the BIOS ROM for the RSDP.
+fn find_rsdp() -> Result<Rsdp, AcpiError> {
+    let ebda_segment = physical_bytes(PhysAddr::new(0x40e), 2)?;
+    let ebda = u64::from(u16::from_le_bytes([ebda_segment[0], ebda_segment[1]])) << 4;
+    let areas = [(ebda, 1024), (0xe0000, 0x20000)];
+
+    for &(start, len) in areas.iter().filter(|&&(start, _)| start != 0) {
+        let area = physical_bytes(PhysAddr::new(start), len)?;
+        for candidate in (0..len).step_by(16).map(|offset| &area[offset..]) {
+            if !candidate.starts_with(RSDP_SIGNATURE)
+                || candidate.len() < mem::size_of::<Rsdp>()
+                || !has_valid_checksum(&candidate[..RSDP_V1_LENGTH])
+            {
+                continue;
+            }
+            let rsdp: Rsdp = unsafe { ptr::read_unaligned(candidate.as_ptr().cast()) };
+            if rsdp.revision >= 2
+                && !has_valid_checksum(&candidate[..(rsdp.length as usize).min(candidate.len())])
+            {
+                continue;
+            }
+            return Ok(rsdp);
+        }
+    }
+    Err(AcpiError::RsdpNotFound)
+}
+
+/// Returns the tables the XSDT, or the RSDT before ACPI 2.0, points to.
+fn root_tables(rsdp: &Rsdp) -> Result<Vec<Table>, AcpiError> {
+    let (root, entry_size) = if rsdp.revision >= 2 && rsdp.xsdt_address != 0 {
+        (Table::at(PhysAddr::new(rsdp.xsdt_address))?, 8)
+    } else {
+        (Table::at(PhysAddr::new(u64::from(rsdp.rsdt_address)))?, 4)
+    };
+
+    let mut tables = Vec::new();
+    for entry in root.data().chunks_exact(entry_size) {
+        let mut address = [0; 8];
+        address[..entry_size].copy_from_slice(entry);
+        match Table::at(PhysAddr::new(u64::from_le_bytes(address))) {
+            Ok(table) => tables.push(table),
+            // one broken table shouldn't hide the others
+            Err(error) => log::warn!("skipping ACPI table: {:?}", error),
+        }
+    }
+    Ok(tables)
+}
+
+/// Finds the ACPI tables, parses the ones the kernel uses and logs a summary.
+///
+/// Needs the heap and `memory::init`.
+pub fn init() -> Result<(), AcpiError> {
+    if TABLES.is_initialized() {
+        return Err(AcpiError::AlreadyInitialized);
+    }
+    let rsdp = find_rsdp()?;
+    let tables = root_tables(&rsdp)?;
+
+    let find = |signature: &[u8; 4]| tables.iter().find(|table| &table.signature() == signature);
+    let madt = find(b"APIC").and_then(|table| parsed(Madt::parse(table)));
+    let fadt = find(b"FACP").and_then(|table| parsed(Fadt::parse(table)));
+    let hpet = find(b"HPET").and_then(|table| parsed(Hpet::parse(table)));
+
+    TABLES.init_once(|| AcpiTables {
+        revision: rsdp.revision,
+        oem_id: rsdp.oem_id,
+        tables,
+        madt,
+        fadt,
+        hpet,
+    });
+    log_summary();
+    Ok(())
+}
+
+/// Keeps a parsed table, or logs why it couldn't be parsed.
+fn parsed<T>(result: Result<T, AcpiError>) -> Option<T> {
+    match result {
+        Ok(table) => Some(table),
+        Err(error) => {
+            log::warn!("skipping ACPI table: {:?}", error);
+            None
+        }
+    }
+}
+
+/// Returns the tables found by `init`.
+pub fn tables() -> Result<&'static AcpiTables, AcpiError> {
+    TABLES.try_get().map_err(|_| AcpiError::NotInitialized)
+}
+
+/// Returns the table with the given signature, once `init` found it.
+pub fn find_table(signature: &[u8; 4]) -> Result<Table, AcpiError> {
+    tables()?
+        .tables
+        .iter()
+        .find(|table| &table.signature() == signature)
+        .copied()
+        .ok_or(AcpiError::TableNotFound(*signature))
+}
+
+pub fn madt() -> Result<&'static Madt, AcpiError> {
+    tables()?
+        .madt
+        .as_ref()
+        .ok_or(AcpiError::TableNotFound(*b"APIC"))
+}
+
+pub fn fadt() -> Result<&'static Fadt, AcpiError> {
+    tables()?
+        .fadt
+        .as_ref()
+        .ok_or(AcpiError::TableNotFound(*b"FACP"))
+}
+
+pub fn hpet() -> Result<&'static Hpet, AcpiError> {
+    tables()?
+        .hpet
+        .as_ref()
+        .ok_or(AcpiError::TableNotFound(*b"HPET"))
+}
+
+fn log_summary() {
+    let tables = match tables() {
+        Ok(tables) => tables,
+        Err(_) => return,
+    };
+
+    log::info!(
+        "ACPI {} by {}",
+        if tables.revision >= 2 { "2.0+" } else { "1.0" },
+        ascii(&tables.oem_id)
+    );
+    for table in &tables.tables {
+        log::info!(
+            "  {} at {:#010x}, {} bytes, {}",
+            ascii(&table.signature()),
+            table.address().as_u64(),
+            { table.header().length },
+            ascii(&table.header().oem_table_id)
+        );
+    }
+    if let Some(madt) = &tables.madt {
+        log::info!(
+            "  MADT: {} processors, {} I/O APICs, {} overrides, local APIC at {:#x}",
+            madt.processors.iter().filter(|cpu| cpu.enabled).count(),
+            madt.io_apics.len(),
+            madt.overrides.len(),
+            madt.local_apic_address.as_u64()
+        );
+    }
+    if let Some(fadt) = &tables.fadt {
+        log::info!(
+            "  FADT: SCI IRQ {}, PM1a control {:#x}, reset register {}",
+            fadt.sci_interrupt,
+            fadt.pm1a_control_block.map_or(0, |block| block.address),
+            if fadt.reset_register.is_some() {
+                "present"
+            } else {
+                "absent"
+            }
+        );
+    }
+    if let Some(hpet) = &tables.hpet {
+        log::info!(
+            "  HPET: {} comparators, {}-bit counter at {:#x}",
+            hpet.comparators,
+            if hpet.counter_64bit { 64 } else { 32 },
+            hpet.base_address.address
+        );
+    }
+}
+
+/// OEM and signature fields are space padded ASCII.
+fn ascii(bytes: &[u8]) -> &str {
+    str::from_utf8(bytes).unwrap_or("?").trim_end()
+}
+
+/// Little endian fields of a table, read without regard to alignment.
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut value = [0; 4];
+    value.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(value)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    let mut value = [0; 8];
+    value.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_le_bytes(value)
+}
@@ -0,0 +1,136 @@
+//! The fixed ACPI description table ("FACP"), which describes the power management
+//! hardware.
+
+use super::{read_u16, read_u32, read_u64, AcpiError, Table};
+use x86_64::PhysAddr;
+
+/// Length of the ACPI 1.0 table, up to and including the flags.
+const MIN_LENGTH: usize = 116;
+
+const RESET_REGISTER_SUPPORTED: u32 = 1 << 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressSpace {
+    Memory,
+    Io,
+    Other(u8),
+}
+
+/// A register given by address space and address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenericAddress {
+    pub address_space: AddressSpace,
+    pub bit_width: u8,
+    pub bit_offset: u8,
+    pub access_size: u8,
+    pub address: u64,
+}
+
+impl GenericAddress {
+    pub(super) fn parse(bytes: &[u8], offset: usize) -> GenericAddress {
+        GenericAddress {
+            address_space: match bytes[offset] {
+                0 => AddressSpace::Memory,
+                1 => AddressSpace::Io,
+                other => AddressSpace::Other(other),
+            },
+            bit_width: bytes[offset + 1],
+            bit_offset: bytes[offset + 2],
+            access_size: bytes[offset + 3],
+            address: read_u64(bytes, offset + 4),
+        }
+    }
+
+    /// An I/O port block from one of the ACPI 1.0 fields, which are plain port numbers.
+    fn io(port: u32, len: u8) -> GenericAddress {
+        GenericAddress {
+            address_space: AddressSpace::Io,
+            bit_width: len * 8,
+            bit_offset: 0,
+            access_size: 0,
+            address: u64::from(port),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fadt {
+    pub dsdt: PhysAddr,
+    /// The ISA IRQ of the system control interrupt.
+    pub sci_interrupt: u16,
+    /// Port to write `acpi_enable` to, to hand the hardware from SMM to the OS.
+    pub smi_command: u32,
+    pub acpi_enable: u8,
+    pub pm1a_event_block: Option<GenericAddress>,
+    pub pm1b_event_block: Option<GenericAddress>,
+    /// Where the sleep type and enable bits of the PM1 control register are written.
+    pub pm1a_control_block: Option<GenericAddress>,
+    pub pm1b_control_block: Option<GenericAddress>,
+    pub pm_timer_block: Option<GenericAddress>,
+    /// The CMOS RTC register with the century, if there is one.
+    pub century: u8,
+    pub flags: u32,
+    /// The register to write `reset_value` to, to reset the machine.
+    pub reset_register: Option<GenericAddress>,
+    pub reset_value: u8,
+}
+
+impl Fadt {
+    pub fn parse(table: &Table) -> Result<Fadt, AcpiError> {
+        let bytes = table.bytes();
+        if bytes.len() < MIN_LENGTH {
+            return Err(AcpiError::TableTooShort(table.signature()));
+        }
+        // the 64-bit fields of ACPI 2.0 take precedence where they are filled in
+        let extended = |offset: usize| {
+            Some(offset)
+                .filter(|&offset| bytes.len() >= offset + 12)
+                .map(|offset| GenericAddress::parse(bytes, offset))
+                .filter(|address| address.address != 0)
+        };
+        let block = |legacy_offset: usize, len_offset: usize, extended_offset: usize| {
+            extended(extended_offset).or_else(|| {
+                Some(read_u32(bytes, legacy_offset))
+                    .filter(|&port| port != 0)
+                    .map(|port| GenericAddress::io(port, bytes[len_offset]))
+            })
+        };
+
+        let flags = read_u32(bytes, 112);
+        let x_dsdt = if bytes.len() >= 148 {
+            read_u64(bytes, 140)
+        } else {
+            0
+        };
+        let dsdt = if x_dsdt != 0 {
+            x_dsdt
+        } else {
+            u64::from(read_u32(bytes, 40))
+        };
+        let reset_register = if bytes.len() >= 129 && flags & RESET_REGISTER_SUPPORTED != 0 {
+            Some(GenericAddress::parse(bytes, 116))
+        } else {
+            None
+        };
+
+        Ok(Fadt {
+            dsdt: PhysAddr::new(dsdt),
+            sci_interrupt: read_u16(bytes, 46),
+            smi_command: read_u32(bytes, 48),
+            acpi_enable: bytes[52],
+            pm1a_event_block: block(56, 88, 148),
+            pm1b_event_block: block(60, 88, 160),
+            pm1a_control_block: block(64, 89, 172),
+            pm1b_control_block: block(68, 89, 184),
+            pm_timer_block: block(76, 91, 208),
+            century: bytes[108],
+            flags,
+            reset_register,
+            reset_value: if reset_register.is_some() {
+                bytes[128]
+            } else {
+                0
+            },
+        })
+    }
+}
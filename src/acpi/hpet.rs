@@ -0,0 +1,39 @@
+//! The high precision event timer description table ("HPET").
+
+use super::{fadt::GenericAddress, read_u16, read_u32, AcpiError, Table};
+
+const LENGTH: usize = 56;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hpet {
+    pub hardware_revision: u8,
+    pub comparators: u8,
+    pub counter_64bit: bool,
+    /// Whether it can take over IRQ0 and IRQ8 from the PIT and the RTC.
+    pub legacy_replacement: bool,
+    pub vendor_id: u16,
+    pub base_address: GenericAddress,
+    pub number: u8,
+    /// Smallest period, in main counter ticks, that periodic mode is guaranteed to handle.
+    pub minimum_tick: u16,
+}
+
+impl Hpet {
+    pub fn parse(table: &Table) -> Result<Hpet, AcpiError> {
+        let bytes = table.bytes();
+        if bytes.len() < LENGTH {
+            return Err(AcpiError::TableTooShort(table.signature()));
+        }
+        let id = read_u32(bytes, 36);
+        Ok(Hpet {
+            hardware_revision: id as u8,
+            comparators: ((id >> 8) & 0x1f) as u8 + 1,
+            counter_64bit: id & (1 << 13) != 0,
+            legacy_replacement: id & (1 << 15) != 0,
+            vendor_id: (id >> 16) as u16,
+            base_address: GenericAddress::parse(bytes, 40),
+            number: bytes[52],
+            minimum_tick: read_u16(bytes, 53),
+        })
+    }
+}
@@ -0,0 +1,147 @@
+//! The multiple APIC description table ("APIC"), which lists the interrupt controllers.
+
+use super::{read_u16, read_u32, read_u64, AcpiError, Table};
+use alloc::vec::Vec;
+use x86_64::PhysAddr;
+
+/// The local APIC address and the flags, before the entries.
+const FIXED_LENGTH: usize = 8;
+
+const PC_AT_COMPATIBLE: u32 = 1 << 0;
+const PROCESSOR_ENABLED: u32 = 1 << 0;
+
+const LOCAL_APIC: u8 = 0;
+const IO_APIC: u8 = 1;
+const INTERRUPT_SOURCE_OVERRIDE: u8 = 2;
+const LOCAL_APIC_ADDRESS_OVERRIDE: u8 = 5;
+const LOCAL_X2APIC: u8 = 9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Polarity {
+    ActiveHigh,
+    ActiveLow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerMode {
+    Edge,
+    Level,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessorApic {
+    pub processor_id: u32,
+    pub apic_id: u32,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoApic {
+    pub id: u8,
+    pub address: PhysAddr,
+    /// The global system interrupt of its first input.
+    pub gsi_base: u32,
+}
+
+/// An ISA IRQ that isn't connected to the I/O APIC input of the same number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterruptOverride {
+    pub irq: u8,
+    pub gsi: u32,
+    pub polarity: Polarity,
+    pub trigger_mode: TriggerMode,
+}
+
+#[derive(Debug, Clone)]
+pub struct Madt {
+    pub local_apic_address: PhysAddr,
+    /// Whether there are 8259 PICs as well, which must be masked to use the APICs.
+    pub pc_at_compatible: bool,
+    pub processors: Vec<ProcessorApic>,
+    pub io_apics: Vec<IoApic>,
+    pub overrides: Vec<InterruptOverride>,
+}
+
+impl Madt {
+    pub fn parse(table: &Table) -> Result<Madt, AcpiError> {
+        let data = table.data();
+        if data.len() < FIXED_LENGTH {
+            return Err(AcpiError::TableTooShort(table.signature()));
+        }
+        let mut madt = Madt {
+            local_apic_address: PhysAddr::new(u64::from(read_u32(data, 0))),
+            pc_at_compatible: read_u32(data, 4) & PC_AT_COMPATIBLE != 0,
+            processors: Vec::new(),
+            io_apics: Vec::new(),
+            overrides: Vec::new(),
+        };
+
+        let mut offset = FIXED_LENGTH;
+        while offset + 2 <= data.len() {
+            let kind = data[offset];
+            let len = usize::from(data[offset + 1]);
+            if len < 2 || offset + len > data.len() {
+                break;
+            }
+            let entry = &data[offset..offset + len];
+            match (kind, len) {
+                (LOCAL_APIC, 8) => madt.processors.push(ProcessorApic {
+                    processor_id: u32::from(entry[2]),
+                    apic_id: u32::from(entry[3]),
+                    enabled: read_u32(entry, 4) & PROCESSOR_ENABLED != 0,
+                }),
+                (IO_APIC, 12) => madt.io_apics.push(IoApic {
+                    id: entry[2],
+                    address: PhysAddr::new(u64::from(read_u32(entry, 4))),
+                    gsi_base: read_u32(entry, 8),
+                }),
+                (INTERRUPT_SOURCE_OVERRIDE, 10) => {
+                    let flags = read_u16(entry, 8);
+                    madt.overrides.push(InterruptOverride {
+                        irq: entry[3],
+                        gsi: read_u32(entry, 4),
+                        // anything but an explicit low or level conforms to the ISA bus
+                        polarity: match flags & 0b11 {
+                            0b11 => Polarity::ActiveLow,
+                            _ => Polarity::ActiveHigh,
+                        },
+                        trigger_mode: match (flags >> 2) & 0b11 {
+                            0b11 => TriggerMode::Level,
+                            _ => TriggerMode::Edge,
+                        },
+                    });
+                }
+                (LOCAL_APIC_ADDRESS_OVERRIDE, 12) => {
+                    madt.local_apic_address = PhysAddr::new(read_u64(entry, 4));
+                }
+                (LOCAL_X2APIC, 16) => madt.processors.push(ProcessorApic {
+                    processor_id: read_u32(entry, 12),
+                    apic_id: read_u32(entry, 4),
+                    enabled: read_u32(entry, 8) & PROCESSOR_ENABLED != 0,
+                }),
+                _ => {}
+            }
+            offset += len;
+        }
+        Ok(madt)
+    }
+
+    /// Returns where an ISA IRQ arrives at the I/O APICs.
+    pub fn isa_irq(&self, irq: u8) -> InterruptOverride {
+        isa_irq(&self.overrides, irq)
+    }
+}
+
+/// Returns where an ISA IRQ arrives at the I/O APICs, given the MADT's overrides.
+pub fn isa_irq(overrides: &[InterruptOverride], irq: u8) -> InterruptOverride {
+    overrides
+        .iter()
+        .find(|entry| entry.irq == irq)
+        .copied()
+        .unwrap_or(InterruptOverride {
+            irq,
+            gsi: u32::from(irq),
+            polarity: Polarity::ActiveHigh,
+            trigger_mode: TriggerMode::Edge,
+        })
+}
@@ -1,3 +1,4 @@
+use crate::sync::{IrqSafeMutex, IrqSafeMutexGuard};
 use fixed_size_block::FixedSizeBlockAllocator;
 use x86_64::{
     structures::paging::{
@@ -16,6 +17,11 @@ static ALLOCATOR: Locked<FixedSizeBlockAllocator> = Locked::new(FixedSizeBlockAl
 pub const HEAP_START: usize = 0x_4444_4444_0000;
 pub const HEAP_SIZE: usize = 100 * 1024;
 
+/// Number of heap bytes currently in use.
+pub fn heap_used() -> usize {
+    ALLOCATOR.lock().used()
+}
+
 pub fn init_heap(
     mapper: &mut impl Mapper<Size4KiB>,
     frame_allocator: &mut impl FrameAllocator<Size4KiB>,
@@ -43,18 +49,21 @@ pub fn init_heap(
     Ok(())
 }
 
+/// Wraps an allocator for `GlobalAlloc`, which only gets `&self`.
+///
+/// Interrupts stay disabled while the allocator is locked, in case a handler allocates.
 pub struct Locked<A> {
-    inner: spin::Mutex<A>,
+    inner: IrqSafeMutex<A>,
 }
 
 impl<A> Locked<A> {
     pub const fn new(inner: A) -> Self {
         Locked {
-            inner: spin::Mutex::new(inner),
+            inner: IrqSafeMutex::new(inner),
         }
     }
 
-    pub fn lock(&self) -> spin::MutexGuard<A> {
+    pub fn lock(&self) -> IrqSafeMutexGuard<A> {
         self.inner.lock()
     }
 }
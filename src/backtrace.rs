@@ -0,0 +1,234 @@
+//! Stack backtraces, walked along the frame pointer chain and resolved against the
+//! symbol table `tools/ksyms.py` writes into the kernel image.
+
+use crate::{memory, sync::IrqSafeMutex};
+use core::{fmt, ptr, slice, str};
+use x86_64::{structures::idt::InterruptStackFrame, VirtAddr};
+
+/// Frames beyond this many are left out.
+pub const MAX_FRAMES: usize = 32;
+
+/// Room for the symbol table, which is filled in after linking.
+const SYMBOLS_SIZE: usize = 512 * 1024;
+const SYMBOLS_MAGIC: [u8; 4] = *b"KSYM";
+const HEADER_SIZE: usize = 12;
+const ENTRY_SIZE: usize = 16;
+
+// Layout, little endian:
+//   magic "KSYM" | count u32 | strings offset u32
+//   count entries sorted by address: address u64 | size u32 | name offset u32
+//   names, each a length byte followed by the demangled name
+#[link_section = ".ksyms"]
+#[used]
+static SYMBOLS: [u8; SYMBOLS_SIZE] = [0; SYMBOLS_SIZE];
+
+/// The backtrace of the exception that is about to panic, for the panic handler.
+static FAULT: IrqSafeMutex<Option<Backtrace>> = IrqSafeMutex::new(None);
+
+#[derive(Clone)]
+pub struct Backtrace {
+    addresses: [u64; MAX_FRAMES],
+    len: usize,
+}
+
+impl Backtrace {
+    /// Walks the stack of the caller.
+    #[inline(never)]
+    pub fn capture() -> Backtrace {
+        let rbp = frame_pointer();
+        let rsp: u64;
+        unsafe { asm!("mov {}, rsp", out(reg) rsp, options(nomem, nostack)) };
+        let mut backtrace = Backtrace::empty();
+        backtrace.walk(rsp, rbp);
+        backtrace
+    }
+
+    /// Walks the stack of code interrupted by an exception, from the registers the
+    /// trap saved.
+    pub fn from_fault(rip: u64, rsp: u64, rbp: u64) -> Backtrace {
+        let mut backtrace = Backtrace::empty();
+        backtrace.push(rip);
+        backtrace.walk(rsp, rbp);
+        backtrace
+    }
+
+    /// Walks the stack of the code an `x86-interrupt` handler interrupted.
+    ///
+    /// Must be called from the handler itself, whose frame links to the interrupted
+    /// one.
+    #[inline(always)]
+    pub fn from_interrupt(stack_frame: &InterruptStackFrame) -> Backtrace {
+        let handler_frame = frame_pointer();
+        let rbp = if is_readable(handler_frame) {
+            unsafe { ptr::read(handler_frame as *const u64) }
+        } else {
+            0
+        };
+        Backtrace::from_fault(
+            stack_frame.instruction_pointer.as_u64(),
+            stack_frame.stack_pointer.as_u64(),
+            rbp,
+        )
+    }
+
+    fn empty() -> Backtrace {
+        Backtrace {
+            addresses: [0; MAX_FRAMES],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, address: u64) -> bool {
+        if self.len == MAX_FRAMES {
+            return false;
+        }
+        self.addresses[self.len] = address;
+        self.len += 1;
+        true
+    }
+
+    /// Follows the saved frame pointers, which only ever lead up the stack.
+    fn walk(&mut self, rsp: u64, mut rbp: u64) {
+        let mut lowest = rsp;
+        while rbp >= lowest && rbp % 8 == 0 && is_readable(rbp) && is_readable(rbp + 8) {
+            let (next, return_address) = unsafe {
+                (
+                    ptr::read(rbp as *const u64),
+                    ptr::read((rbp + 8) as *const u64),
+                )
+            };
+            if return_address == 0 || !self.push(return_address) {
+                break;
+            }
+            lowest = rbp + 16;
+            rbp = next;
+        }
+    }
+
+    /// The instruction addresses, innermost first; all but a fault's are return
+    /// addresses.
+    pub fn addresses(&self) -> &[u64] {
+        &self.addresses[..self.len]
+    }
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Backtrace:")?;
+        for (index, &address) in self.addresses().iter().enumerate() {
+            write!(f, "{:>4}: {:#018x}", index, address)?;
+            // a return address may already be past the end of its caller
+            match symbolize(address.saturating_sub(1)) {
+                Some((name, offset)) => writeln!(f, " - {}+{:#x}", name, offset + 1)?,
+                None => writeln!(f, " - <unknown>")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Keeps the backtrace of an exception for `panic_backtrace`, before panicking.
+pub fn record_fault(backtrace: Backtrace) {
+    if let Some(mut fault) = FAULT.try_lock() {
+        *fault = Some(backtrace);
+    }
+}
+
+/// The backtrace the panic handler prints: the recorded exception's, or else that of
+/// the panic.
+pub fn panic_backtrace() -> Backtrace {
+    match FAULT.try_lock().and_then(|mut fault| fault.take()) {
+        Some(backtrace) => backtrace,
+        None => Backtrace::capture(),
+    }
+}
+
+#[inline(always)]
+fn frame_pointer() -> u64 {
+    let rbp: u64;
+    unsafe { asm!("mov {}, rbp", out(reg) rbp, options(nomem, nostack)) };
+    rbp
+}
+
+/// Whether `address` is mapped; before `memory::init` there is no way to tell, and
+/// the walk has to trust the frame chain.
+fn is_readable(address: u64) -> bool {
+    match VirtAddr::try_new(address) {
+        Ok(address) if memory::physical_memory_offset().is_some() => {
+            memory::page_flags(address).is_some()
+        }
+        Ok(_) => true,
+        Err(_) => false,
+    }
+}
+
+/// Returns the function containing `address` and the offset into it.
+pub fn symbolize(address: u64) -> Option<(&'static str, u64)> {
+    let table = symbol_table()?;
+    let count = read_u32(table, 4)? as usize;
+    let strings = read_u32(table, 8)? as usize;
+    let entry = |index: usize| {
+        let offset = HEADER_SIZE + index * ENTRY_SIZE;
+        Some((
+            read_u64(table, offset)?,
+            read_u32(table, offset + 8)?,
+            read_u32(table, offset + 12)?,
+        ))
+    };
+
+    // the last symbol starting at or before `address`
+    let (mut low, mut high) = (0, count);
+    while low < high {
+        let middle = (low + high) / 2;
+        if entry(middle)?.0 <= address {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    let (start, size, name) = entry(low.checked_sub(1)?)?;
+    if address - start >= u64::from(size.max(1)) {
+        return None;
+    }
+    let name = strings + name as usize;
+    let len = usize::from(*table.get(name)?);
+    let name = str::from_utf8(table.get(name + 1..name + 1 + len)?).ok()?;
+    Some((name, address - start))
+}
+
+/// The symbol table, if the image has one.
+fn symbol_table() -> Option<&'static [u8]> {
+    let mut table = SYMBOLS.as_ptr();
+    // the compiler would otherwise read the all-zero initializer rather than the image
+    unsafe { asm!("/* {} */", inout(reg) table, options(pure, nomem, nostack)) };
+    let table = unsafe { slice::from_raw_parts(table, SYMBOLS_SIZE) };
+    if table[..4] == SYMBOLS_MAGIC {
+        Some(table)
+    } else {
+        None
+    }
+}
+
+fn read_u32(table: &[u8], offset: usize) -> Option<u32> {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(table.get(offset..offset + 4)?);
+    Some(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(table: &[u8], offset: usize) -> Option<u64> {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(table.get(offset..offset + 8)?);
+    Some(u64::from_le_bytes(bytes))
+}
+
+#[test_case]
+fn test_capture_walks_the_callers() {
+    #[inline(never)]
+    fn nested() -> Backtrace {
+        Backtrace::capture()
+    }
+
+    let backtrace = nested();
+    assert!(backtrace.addresses().len() >= 2);
+    assert!(backtrace.addresses().iter().all(|&address| address != 0));
+}
@@ -0,0 +1,132 @@
+use crate::sync::IrqSafeMutex;
+use crate::{
+    serial::ComPort,
+    vga_buffer::{self, ColorCode},
+};
+use core::fmt;
+
+pub mod debugcon;
+pub mod serial;
+pub mod vga;
+
+/// An output device that `print!` and `colored_print!` write to.
+pub trait Console: Sync {
+    /// Writes the formatted text, in the given colors if the device can show them.
+    fn write(&self, color_code: Option<ColorCode>, args: fmt::Arguments);
+}
+
+/// Writes to every enabled console, like `print!`.
+pub struct AllConsoles;
+
+impl Console for AllConsoles {
+    fn write(&self, color_code: Option<ColorCode>, args: fmt::Arguments) {
+        _print(color_code, args);
+    }
+}
+
+/// Handle of a registered console, used to turn it on or off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConsoleId(usize);
+
+/// The VGA text mode kernel console.
+pub const VGA: ConsoleId = ConsoleId(0);
+/// The first serial port.
+pub const SERIAL: ConsoleId = ConsoleId(1);
+/// QEMU's debug console on port 0xe9, shown with `-debugcon stdio`.
+pub const DEBUGCON: ConsoleId = ConsoleId(2);
+
+const MAX_CONSOLES: usize = 8;
+
+#[derive(Debug)]
+pub enum RegisterError {
+    RegistryFull,
+}
+
+#[derive(Clone, Copy)]
+struct Sink {
+    console: &'static dyn Console,
+    enabled: bool,
+}
+
+static SINKS: IrqSafeMutex<[Option<Sink>; MAX_CONSOLES]> = IrqSafeMutex::new([
+    Some(Sink {
+        console: &vga::VgaConsole,
+        enabled: true,
+    }),
+    Some(Sink {
+        console: &serial::SerialConsole(ComPort::Com1),
+        enabled: true,
+    }),
+    Some(Sink {
+        console: &debugcon::DebugconConsole,
+        enabled: true,
+    }),
+    None,
+    None,
+    None,
+    None,
+    None,
+]);
+
+/// Adds a console that receives all further output while it is enabled.
+pub fn register(console: &'static dyn Console, enabled: bool) -> Result<ConsoleId, RegisterError> {
+    let mut sinks = SINKS.lock();
+    let index = sinks
+        .iter()
+        .position(Option::is_none)
+        .ok_or(RegisterError::RegistryFull)?;
+    sinks[index] = Some(Sink { console, enabled });
+    Ok(ConsoleId(index))
+}
+
+pub fn set_enabled(id: ConsoleId, enabled: bool) {
+    if let Some(sink) = &mut SINKS.lock()[id.0] {
+        sink.enabled = enabled;
+    }
+}
+
+pub fn is_enabled(id: ConsoleId) -> bool {
+    SINKS.lock()[id.0].map_or(false, |sink| sink.enabled)
+}
+
+/// Writes `args` to a terminal-like byte stream, turning the VGA colors into ANSI escape codes.
+fn write_ansi(
+    writer: &mut impl fmt::Write,
+    color_code: Option<ColorCode>,
+    args: fmt::Arguments,
+) -> fmt::Result {
+    match color_code {
+        Some(color_code) => {
+            let (foreground, background) = vga_buffer::sgr_params(color_code);
+            write!(writer, "\x1b[{};{}m", foreground, background)?;
+            writer.write_fmt(args)?;
+            writer.write_str("\x1b[0m")
+        }
+        None => writer.write_fmt(args),
+    }
+}
+
+#[doc(hidden)]
+pub fn _print(color_code: Option<ColorCode>, args: fmt::Arguments) {
+    // copy the registry so that a console may print (or panic) without deadlocking
+    let sinks = *SINKS.lock();
+    for sink in sinks.iter().flatten().filter(|sink| sink.enabled) {
+        sink.console.write(color_code, args);
+    }
+}
+
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => ($crate::console::_print(None, format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! println {
+    () => ($crate::print!("\n"));
+    ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! colored_print {
+    ($color_code:expr, $($arg:tt)*) => ($crate::console::_print(Some($color_code), format_args!($($arg)*)));
+}
@@ -0,0 +1,31 @@
+use super::{write_ansi, Console};
+use crate::{sync::IrqSafeMutex, vga_buffer::ColorCode};
+use core::fmt;
+use x86_64::instructions::port::Port;
+
+/// Port of the QEMU and Bochs debug console; writes are ignored elsewhere.
+const DEBUGCON_PORT: u16 = 0xe9;
+
+static PORT: IrqSafeMutex<Port<u8>> = IrqSafeMutex::new(Port::new(DEBUGCON_PORT));
+
+/// Writes to the 0xe9 debug console, with colors sent as ANSI escape codes.
+pub struct DebugconConsole;
+
+struct PortWriter<'a>(&'a mut Port<u8>);
+
+impl fmt::Write for PortWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            unsafe { self.0.write(byte) };
+        }
+        Ok(())
+    }
+}
+
+impl Console for DebugconConsole {
+    fn write(&self, color_code: Option<ColorCode>, args: fmt::Arguments) {
+        let mut port = PORT.lock();
+        write_ansi(&mut PortWriter(&mut port), color_code, args)
+            .expect("Printing to debugcon failed");
+    }
+}
@@ -0,0 +1,16 @@
+use super::{write_ansi, Console};
+use crate::{
+    serial::{self, ComPort},
+    vga_buffer::ColorCode,
+};
+use core::fmt;
+
+/// Writes to a serial port, with colors sent as ANSI escape codes.
+pub struct SerialConsole(pub ComPort);
+
+impl Console for SerialConsole {
+    fn write(&self, color_code: Option<ColorCode>, args: fmt::Arguments) {
+        write_ansi(&mut *serial::port(self.0).lock(), color_code, args)
+            .expect("Printing to serial failed");
+    }
+}
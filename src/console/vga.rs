@@ -0,0 +1,12 @@
+use super::Console;
+use crate::vga_buffer::{self, ColorCode};
+use core::fmt;
+
+/// Writes to the kernel console of the VGA text buffer.
+pub struct VgaConsole;
+
+impl Console for VgaConsole {
+    fn write(&self, color_code: Option<ColorCode>, args: fmt::Arguments) {
+        vga_buffer::_print(color_code, args);
+    }
+}
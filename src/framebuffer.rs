@@ -0,0 +1,255 @@
+use crate::{
+    console::{Console, RegisterError},
+    sync::IrqSafeMutex,
+    vga_buffer::{Color, ColorCode},
+};
+use console::FramebufferConsole;
+use core::{
+    fmt, ptr, slice,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use x86_64::{
+    structures::paging::{
+        mapper::MapToError, FrameAllocator, Mapper, Page, PageTableFlags, PhysFrame, Size4KiB,
+    },
+    PhysAddr, VirtAddr,
+};
+
+pub mod bga;
+pub mod console;
+pub mod font;
+
+pub const FRAMEBUFFER_START: usize = 0x_5555_5555_0000;
+
+const BITS_PER_PIXEL: u16 = 32;
+const BYTES_PER_PIXEL: usize = 4;
+
+#[derive(Debug)]
+pub enum InitError {
+    AdapterNotFound,
+    MapFailed(MapToError<Size4KiB>),
+    ConsoleRegistryFull,
+}
+
+impl From<MapToError<Size4KiB>> for InitError {
+    fn from(error: MapToError<Size4KiB>) -> Self {
+        InitError::MapFailed(error)
+    }
+}
+
+impl From<RegisterError> for InitError {
+    fn from(error: RegisterError) -> Self {
+        match error {
+            RegisterError::RegistryFull => InitError::ConsoleRegistryFull,
+        }
+    }
+}
+
+/// Console sink that draws `print!` output on the framebuffer.
+struct FramebufferSink;
+
+impl Console for FramebufferSink {
+    fn write(&self, color_code: Option<ColorCode>, args: fmt::Arguments) {
+        _print(color_code, args);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl Rgb {
+    pub const fn new(red: u8, green: u8, blue: u8) -> Rgb {
+        Rgb { red, green, blue }
+    }
+
+    fn to_pixel(self) -> u32 {
+        u32::from(self.red) << 16 | u32::from(self.green) << 8 | u32::from(self.blue)
+    }
+}
+
+impl From<Color> for Rgb {
+    /// Maps the text mode colors to the standard VGA palette.
+    fn from(color: Color) -> Rgb {
+        match color {
+            Color::Black => Rgb::new(0x00, 0x00, 0x00),
+            Color::Blue => Rgb::new(0x00, 0x00, 0xaa),
+            Color::Green => Rgb::new(0x00, 0xaa, 0x00),
+            Color::Cyan => Rgb::new(0x00, 0xaa, 0xaa),
+            Color::Red => Rgb::new(0xaa, 0x00, 0x00),
+            Color::Magenta => Rgb::new(0xaa, 0x00, 0xaa),
+            Color::Brown => Rgb::new(0xaa, 0x55, 0x00),
+            Color::LightGray => Rgb::new(0xaa, 0xaa, 0xaa),
+            Color::DarkGray => Rgb::new(0x55, 0x55, 0x55),
+            Color::LightBlue => Rgb::new(0x55, 0x55, 0xff),
+            Color::LightGreen => Rgb::new(0x55, 0xff, 0x55),
+            Color::LightCyan => Rgb::new(0x55, 0xff, 0xff),
+            Color::LightRed => Rgb::new(0xff, 0x55, 0x55),
+            Color::Pink => Rgb::new(0xff, 0x55, 0xff),
+            Color::Yellow => Rgb::new(0xff, 0xff, 0x55),
+            Color::White => Rgb::new(0xff, 0xff, 0xff),
+        }
+    }
+}
+
+/// A 32 bit linear framebuffer; coordinates outside of it are ignored.
+pub struct Framebuffer {
+    pixels: &'static mut [u32],
+    width: usize,
+    height: usize,
+}
+
+impl Framebuffer {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Rgb) {
+        if x < self.width && y < self.height {
+            let pixel = &mut self.pixels[y * self.width + x];
+            unsafe { ptr::write_volatile(pixel, color.to_pixel()) };
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Option<Rgb> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let value = unsafe { ptr::read_volatile(&self.pixels[y * self.width + x]) };
+        Some(Rgb::new(
+            (value >> 16) as u8,
+            (value >> 8) as u8,
+            value as u8,
+        ))
+    }
+
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: Rgb) {
+        for row in y..(y + height).min(self.height) {
+            for col in x..(x + width).min(self.width) {
+                self.set_pixel(col, row, color);
+            }
+        }
+    }
+
+    /// Draws a line with Bresenham's algorithm.
+    pub fn draw_line(&mut self, from: (usize, usize), to: (usize, usize), color: Rgb) {
+        let (mut x, mut y) = (from.0 as isize, from.1 as isize);
+        let (end_x, end_y) = (to.0 as isize, to.1 as isize);
+        let dx = (end_x - x).abs();
+        let dy = -(end_y - y).abs();
+        let step_x = if x < end_x { 1 } else { -1 };
+        let step_y = if y < end_y { 1 } else { -1 };
+        let mut error = dx + dy;
+        loop {
+            self.set_pixel(x as usize, y as usize, color);
+            if x == end_x && y == end_y {
+                break;
+            }
+            let doubled_error = 2 * error;
+            if doubled_error >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled_error <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    pub fn clear(&mut self, color: Rgb) {
+        self.fill_rect(0, 0, self.width, self.height, color);
+    }
+
+    /// Moves the picture up by `lines` pixel rows, filling the freed rows with `color`.
+    pub fn scroll_up(&mut self, lines: usize, color: Rgb) {
+        let lines = lines.min(self.height);
+        self.pixels.copy_within(lines * self.width.., 0);
+        self.fill_rect(0, self.height - lines, self.width, lines, color);
+    }
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static CONSOLE: IrqSafeMutex<Option<FramebufferConsole>> = IrqSafeMutex::new(None);
+
+/// Switches the Bochs graphics adapter to a `width`x`height` linear framebuffer, maps it at
+/// `FRAMEBUFFER_START` and sends `print!` and `colored_print!` to a console drawn on it.
+pub fn init(
+    width: u16,
+    height: u16,
+    mapper: &mut impl Mapper<Size4KiB>,
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>,
+) -> Result<(), InitError> {
+    if !bga::is_present() {
+        return Err(InitError::AdapterNotFound);
+    }
+    let pixel_count = usize::from(width) * usize::from(height);
+    let page_range = {
+        let start = VirtAddr::new(FRAMEBUFFER_START as u64);
+        let end = start + pixel_count * BYTES_PER_PIXEL - 1u64;
+        Page::range_inclusive(
+            Page::containing_address(start),
+            Page::containing_address(end),
+        )
+    };
+    let physical_start = PhysAddr::new(bga::framebuffer_address());
+    for (index, page) in page_range.enumerate() {
+        let frame = PhysFrame::containing_address(physical_start + index as u64 * 4096);
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_CACHE;
+        unsafe { mapper.map_to(page, frame, flags, frame_allocator)?.flush() };
+    }
+
+    bga::set_mode(width, height, BITS_PER_PIXEL);
+    let sink = match crate::console::register(&FramebufferSink, false) {
+        Ok(sink) => sink,
+        Err(error) => {
+            bga::disable();
+            return Err(error.into());
+        }
+    };
+    let pixels = unsafe { slice::from_raw_parts_mut(FRAMEBUFFER_START as *mut u32, pixel_count) };
+    let framebuffer = Framebuffer {
+        pixels,
+        width: usize::from(width),
+        height: usize::from(height),
+    };
+
+    *CONSOLE.lock() = Some(FramebufferConsole::new(framebuffer));
+    ENABLED.store(true, Ordering::SeqCst);
+    crate::console::set_enabled(sink, true);
+    crate::console::set_enabled(crate::console::VGA, false);
+    Ok(())
+}
+
+/// Whether `init` has switched the display to the framebuffer console.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// Runs `f` on the framebuffer for drawing, if graphics mode is enabled.
+pub fn with_framebuffer<R>(f: impl FnOnce(&mut Framebuffer) -> R) -> Option<R> {
+    CONSOLE.lock().as_mut().map(|c| f(c.framebuffer()))
+}
+
+#[doc(hidden)]
+pub fn _print(color_code: Option<ColorCode>, args: fmt::Arguments) {
+    use core::fmt::Write;
+
+    if let Some(console) = CONSOLE.lock().as_mut() {
+        let original_color_code = console.color_code();
+        console.set_color_code(color_code.unwrap_or(original_color_code));
+        console
+            .write_fmt(args)
+            .expect("Printing to framebuffer failed");
+        if color_code.is_some() {
+            console.set_color_code(original_color_code);
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use crate::pci;
+use x86_64::instructions::port::Port;
+
+const INDEX_PORT: u16 = 0x1ce;
+const DATA_PORT: u16 = 0x1cf;
+
+const INDEX_ID: u16 = 0x0;
+const INDEX_X_RESOLUTION: u16 = 0x1;
+const INDEX_Y_RESOLUTION: u16 = 0x2;
+const INDEX_BITS_PER_PIXEL: u16 = 0x3;
+const INDEX_ENABLE: u16 = 0x4;
+const INDEX_VIRTUAL_WIDTH: u16 = 0x6;
+const INDEX_VIRTUAL_HEIGHT: u16 = 0x7;
+const INDEX_X_OFFSET: u16 = 0x8;
+const INDEX_Y_OFFSET: u16 = 0x9;
+
+/// Oldest interface version with 32 bit color and a linear framebuffer.
+const ID_MIN: u16 = 0xb0c2;
+const ID_MAX: u16 = 0xb0c5;
+
+const ENABLE_DISPLAY: u16 = 0x01;
+const ENABLE_LINEAR_FRAMEBUFFER: u16 = 0x40;
+
+/// PCI IDs of QEMU's standard VGA card.
+const PCI_VENDOR_ID: u16 = 0x1234;
+const PCI_DEVICE_ID: u16 = 0x1111;
+
+/// Where the framebuffer lives when the adapter is not found on the PCI bus.
+const FIXED_FRAMEBUFFER_ADDRESS: u64 = 0xe000_0000;
+
+fn read_register(index: u16) -> u16 {
+    let mut index_port = Port::new(INDEX_PORT);
+    let mut data_port = Port::new(DATA_PORT);
+    unsafe {
+        index_port.write(index);
+        data_port.read()
+    }
+}
+
+fn write_register(index: u16, value: u16) {
+    let mut index_port = Port::<u16>::new(INDEX_PORT);
+    let mut data_port = Port::<u16>::new(DATA_PORT);
+    unsafe {
+        index_port.write(index);
+        data_port.write(value);
+    }
+}
+
+/// Whether a Bochs graphics adapter that supports a linear framebuffer is present.
+pub fn is_present() -> bool {
+    let id = read_register(INDEX_ID);
+    (ID_MIN..=ID_MAX).contains(&id)
+}
+
+/// Physical address of the linear framebuffer.
+pub fn framebuffer_address() -> u64 {
+    pci::find_device(PCI_VENDOR_ID, PCI_DEVICE_ID)
+        .map(|device| u64::from(device.memory_bar_address(0)))
+        .filter(|&address| address != 0)
+        .unwrap_or(FIXED_FRAMEBUFFER_ADDRESS)
+}
+
+pub fn set_mode(width: u16, height: u16, bits_per_pixel: u16) {
+    write_register(INDEX_ENABLE, 0);
+    write_register(INDEX_X_RESOLUTION, width);
+    write_register(INDEX_Y_RESOLUTION, height);
+    write_register(INDEX_BITS_PER_PIXEL, bits_per_pixel);
+    write_register(INDEX_VIRTUAL_WIDTH, width);
+    write_register(INDEX_VIRTUAL_HEIGHT, height);
+    write_register(INDEX_X_OFFSET, 0);
+    write_register(INDEX_Y_OFFSET, 0);
+    write_register(INDEX_ENABLE, ENABLE_DISPLAY | ENABLE_LINEAR_FRAMEBUFFER);
+}
+
+/// Switches the adapter off, which hands the display back to VGA text mode.
+pub fn disable() {
+    write_register(INDEX_ENABLE, 0);
+}
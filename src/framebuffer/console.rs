@@ -0,0 +1,141 @@
+use super::{
+    font::{self, GLYPH_HEIGHT, GLYPH_WIDTH},
+    Framebuffer, Rgb,
+};
+use crate::color_code;
+use crate::vga_buffer::{self, ansi::Action, ansi::Parser, ColorCode};
+use core::fmt;
+
+const TAB_WIDTH: usize = 8;
+
+/// Text console drawn with the built-in bitmap font onto the framebuffer.
+pub struct FramebufferConsole {
+    framebuffer: Framebuffer,
+    columns: usize,
+    rows: usize,
+    row_position: usize,
+    column_position: usize,
+    color_code: ColorCode,
+    bold: bool,
+    parser: Parser,
+}
+
+impl FramebufferConsole {
+    pub fn new(mut framebuffer: Framebuffer) -> FramebufferConsole {
+        let color_code = color_code!();
+        framebuffer.clear(Rgb::from(color_code.background()));
+        FramebufferConsole {
+            columns: framebuffer.width() / GLYPH_WIDTH,
+            rows: framebuffer.height() / GLYPH_HEIGHT,
+            framebuffer,
+            row_position: 0,
+            column_position: 0,
+            color_code,
+            bold: false,
+            parser: Parser::new(),
+        }
+    }
+
+    pub fn framebuffer(&mut self) -> &mut Framebuffer {
+        &mut self.framebuffer
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn color_code(&self) -> ColorCode {
+        self.color_code
+    }
+
+    pub fn set_color_code(&mut self, color_code: ColorCode) {
+        self.color_code = color_code;
+    }
+
+    /// Writes the string, handling newlines and ANSI colors like the VGA writer does.
+    pub fn write_string(&mut self, s: &str) {
+        for character in s.chars() {
+            match self.parser.advance(character) {
+                Some(Action::Print(character)) => self.write_char(character),
+                Some(Action::Execute(byte)) => self.execute(byte),
+                Some(Action::Csi(csi)) if !csi.is_private() => match csi.final_byte() {
+                    b'm' => {
+                        self.color_code = vga_buffer::select_graphic_rendition(
+                            self.color_code,
+                            &mut self.bold,
+                            csi.params(),
+                        )
+                    }
+                    b'J' if csi.param(0, 0) == 2 => self.clear(),
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.framebuffer
+            .clear(Rgb::from(self.color_code.background()));
+        self.row_position = 0;
+        self.column_position = 0;
+    }
+
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            b'\n' => self.new_line(),
+            b'\r' => self.column_position = 0,
+            b'\t' => {
+                let next_stop = (self.column_position / TAB_WIDTH + 1) * TAB_WIDTH;
+                self.column_position = next_stop.min(self.columns - 1);
+            }
+            0x08 => self.column_position = self.column_position.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    fn write_char(&mut self, character: char) {
+        if self.column_position >= self.columns {
+            self.new_line();
+        }
+        self.draw_glyph(self.row_position, self.column_position, character);
+        self.column_position += 1;
+    }
+
+    fn draw_glyph(&mut self, row: usize, col: usize, character: char) {
+        let foreground = Rgb::from(self.color_code.foreground());
+        let background = Rgb::from(self.color_code.background());
+        let (left, top) = (col * GLYPH_WIDTH, row * GLYPH_HEIGHT);
+        for (y, &bits) in font::glyph(character).iter().enumerate() {
+            for x in 0..GLYPH_WIDTH {
+                let color = if bits & (0x80 >> x) != 0 {
+                    foreground
+                } else {
+                    background
+                };
+                self.framebuffer.set_pixel(left + x, top + y, color);
+            }
+        }
+    }
+
+    fn new_line(&mut self) {
+        if self.row_position < self.rows - 1 {
+            self.row_position += 1;
+        } else {
+            let background = Rgb::from(self.color_code.background());
+            self.framebuffer.scroll_up(GLYPH_HEIGHT, background);
+        }
+        self.column_position = 0;
+    }
+}
+
+impl fmt::Write for FramebufferConsole {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_string(s);
+        Ok(())
+    }
+}
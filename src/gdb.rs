@@ -0,0 +1,418 @@
+//! A GDB remote serial protocol stub on COM2.
+//!
+//! Start QEMU with a second serial port, e.g. `-serial stdio -serial tcp::4321,server,nowait`,
+//! and attach with `target remote :4321`. The kernel stops whenever a breakpoint is hit,
+//! a single step completes or GDB interrupts it.
+
+use crate::{
+    interrupts::TrapFrame,
+    memory,
+    serial::{self, ComPort, LineSettings, SerialError, Uart},
+    sync::IrqSafeMutex,
+};
+use core::sync::atomic::{AtomicBool, Ordering};
+use packet::{parse_hex, parse_hex_byte, parse_hex_le, Connection, Reply, MAX_PACKET_SIZE};
+use x86_64::VirtAddr;
+
+mod packet;
+
+/// The serial port GDB is attached to.
+pub const GDB_PORT: ComPort = ComPort::Com2;
+
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+
+/// Bit of RFLAGS that raises a debug exception after the next instruction.
+const TRAP_FLAG: u64 = 1 << 8;
+/// DR6 with none of the breakpoint and single step status bits set.
+const DEBUG_STATUS_CLEAR: u64 = 0xffff_0ff0;
+const INT3: u8 = 0xcc;
+const MAX_BREAKPOINTS: usize = 32;
+
+/// Number of registers in GDB's amd64 register set, without the FPU and SSE ones.
+const REGISTER_COUNT: usize = 24;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static STATE: IrqSafeMutex<State> = IrqSafeMutex::new(State::new());
+
+#[derive(Clone, Copy)]
+struct Breakpoint {
+    address: u64,
+    original: u8,
+}
+
+struct State {
+    packet: [u8; MAX_PACKET_SIZE],
+    reply: Reply,
+    breakpoints: [Option<Breakpoint>; MAX_BREAKPOINTS],
+    /// The stub's own handle on `GDB_PORT`, as a trap may hit code that holds the
+    /// lock of `serial::port`.
+    uart: Uart,
+}
+
+impl State {
+    const fn new() -> State {
+        State {
+            packet: [0; MAX_PACKET_SIZE],
+            reply: Reply::new(),
+            breakpoints: [None; MAX_BREAKPOINTS],
+            uart: Uart::new(GDB_PORT.base()),
+        }
+    }
+}
+
+/// What to do once a command has been handled.
+enum Resume {
+    /// Stay stopped and send the reply.
+    Stay,
+    Continue,
+    Step,
+    Detach,
+}
+
+/// Sets up `GDB_PORT` and lets breakpoints and debug exceptions stop in the stub.
+pub fn init() -> Result<(), SerialError> {
+    let settings = LineSettings {
+        baud_rate: 115_200,
+        ..LineSettings::new()
+    };
+    serial::configure(GDB_PORT, settings)?;
+    serial::enable_receive_interrupt(GDB_PORT);
+    ENABLED.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// Stops in the debugger, as if a breakpoint had been hit.
+pub fn breakpoint() {
+    x86_64::instructions::interrupts::int3();
+}
+
+/// Called for breakpoint and debug exceptions while the stub is enabled.
+pub(crate) fn handle_trap(frame: &mut TrapFrame) {
+    enter(frame, SIGTRAP, false);
+}
+
+/// Called for the interrupts of `GDB_PORT`, which GDB uses to stop a running kernel.
+pub(crate) fn handle_interrupt(frame: &mut TrapFrame) {
+    match serial::receive(GDB_PORT) {
+        Some(packet::INTERRUPT) => enter(frame, SIGINT, false),
+        // GDB attaches by sending packets to the running kernel
+        Some(b'$') => enter(frame, SIGINT, true),
+        _ => {}
+    }
+}
+
+/// Serves GDB until it continues, steps or detaches.
+///
+/// If `packet_started` is set, the interrupt handler already read the start of a packet.
+fn enter(frame: &mut TrapFrame, signal: u8, packet_started: bool) {
+    let mut state = STATE.lock();
+    let State {
+        packet,
+        reply,
+        breakpoints,
+        uart,
+    } = &mut *state;
+    let mut connection = Connection::new(uart);
+
+    if !packet_started {
+        reply.clear();
+        push_stop_reply(reply, signal);
+        connection.send(reply.as_bytes());
+    }
+
+    let mut started = packet_started;
+    let resume = loop {
+        let request = connection.receive(packet, started);
+        started = false;
+        reply.clear();
+        match handle_command(request, frame, signal, reply, breakpoints) {
+            Resume::Stay => connection.send(reply.as_bytes()),
+            Resume::Detach => {
+                connection.send(b"OK");
+                break Resume::Detach;
+            }
+            resume => break resume,
+        }
+    };
+
+    match resume {
+        Resume::Step => frame.rflags |= TRAP_FLAG,
+        _ => frame.rflags &= !TRAP_FLAG,
+    }
+    // the status bits are sticky, so the next debug exception could not be told apart
+    unsafe { asm!("mov dr6, {}", in(reg) DEBUG_STATUS_CLEAR, options(nomem, nostack)) };
+}
+
+fn handle_command(
+    request: &[u8],
+    frame: &mut TrapFrame,
+    signal: u8,
+    reply: &mut Reply,
+    breakpoints: &mut [Option<Breakpoint>],
+) -> Resume {
+    let (&command, arguments) = match request.split_first() {
+        Some(parts) => parts,
+        None => return Resume::Stay,
+    };
+    match command {
+        b'?' => push_stop_reply(reply, signal),
+        b'g' => {
+            for number in 0..REGISTER_COUNT {
+                if let Some((value, size)) = read_register(frame, number) {
+                    reply.push_hex_le(value, size);
+                }
+            }
+        }
+        b'G' => reply.push_str(result(write_registers(frame, arguments))),
+        b'p' => match parse_hex(arguments).and_then(|n| read_register(frame, n as usize)) {
+            Some((value, size)) => reply.push_hex_le(value, size),
+            None => reply.push_str("E01"),
+        },
+        b'P' => reply.push_str(result(write_register_command(frame, arguments))),
+        b'm' => match parse_address_and_length(arguments) {
+            Some((address, length)) if length * 2 <= MAX_PACKET_SIZE => {
+                if !push_memory(reply, address, length) {
+                    reply.clear();
+                    reply.push_str("E14");
+                }
+            }
+            _ => reply.push_str("E01"),
+        },
+        b'M' => reply.push_str(result(write_memory_command(arguments))),
+        b'c' | b's' => {
+            if !arguments.is_empty() {
+                match parse_hex(arguments) {
+                    Some(address) => frame.rip = address,
+                    None => {
+                        reply.push_str("E01");
+                        return Resume::Stay;
+                    }
+                }
+            }
+            return if command == b'c' {
+                Resume::Continue
+            } else {
+                Resume::Step
+            };
+        }
+        b'Z' | b'z' if arguments.starts_with(b"0,") => {
+            let insert = command == b'Z';
+            let done = parse_address_and_length(&arguments[2..]).map_or(false, |(address, _)| {
+                set_breakpoint(breakpoints, address, insert)
+            });
+            reply.push_str(if done { "OK" } else { "E01" });
+        }
+        b'D' => return Resume::Detach,
+        b'k' => return Resume::Continue,
+        b'H' | b'T' => reply.push_str("OK"),
+        b'q' if request.starts_with(b"qSupported") => {
+            reply.push_str("PacketSize=");
+            reply.push_hex(MAX_PACKET_SIZE as u64);
+        }
+        b'q' if request == b"qAttached" => reply.push_str("1"),
+        // an empty reply tells GDB the command is not supported
+        _ => {}
+    }
+    Resume::Stay
+}
+
+fn push_stop_reply(reply: &mut Reply, signal: u8) {
+    reply.push_str("S");
+    reply.push_hex_byte(signal);
+}
+
+fn result(ok: bool) -> &'static str {
+    if ok {
+        "OK"
+    } else {
+        "E01"
+    }
+}
+
+/// Returns the saved register with the given GDB number and its size in bytes.
+fn read_register(frame: &mut TrapFrame, number: usize) -> Option<(u64, usize)> {
+    match number {
+        0..=17 => writable_register(frame, number).map(|(value, size)| (*value, size)),
+        18 => Some((frame.cs, 4)),
+        19 => Some((frame.ss, 4)),
+        // ds, es, fs and gs are unused in long mode
+        20..=23 => Some((0, 4)),
+        _ => None,
+    }
+}
+
+/// The registers GDB may change; the segment registers are left alone.
+fn writable_register(frame: &mut TrapFrame, number: usize) -> Option<(&mut u64, usize)> {
+    let register = match number {
+        0 => &mut frame.rax,
+        1 => &mut frame.rbx,
+        2 => &mut frame.rcx,
+        3 => &mut frame.rdx,
+        4 => &mut frame.rsi,
+        5 => &mut frame.rdi,
+        6 => &mut frame.rbp,
+        7 => &mut frame.rsp,
+        8 => &mut frame.r8,
+        9 => &mut frame.r9,
+        10 => &mut frame.r10,
+        11 => &mut frame.r11,
+        12 => &mut frame.r12,
+        13 => &mut frame.r13,
+        14 => &mut frame.r14,
+        15 => &mut frame.r15,
+        16 => &mut frame.rip,
+        17 => return Some((&mut frame.rflags, 4)),
+        _ => return None,
+    };
+    Some((register, 8))
+}
+
+fn write_registers(frame: &mut TrapFrame, mut values: &[u8]) -> bool {
+    for number in 0..REGISTER_COUNT {
+        let size = if number < 17 { 8 } else { 4 };
+        if values.len() < size * 2 {
+            break;
+        }
+        let (digits, rest) = values.split_at(size * 2);
+        values = rest;
+        let value = match parse_hex_le(digits, size) {
+            Some(value) => value,
+            None => return false,
+        };
+        if let Some((register, _)) = writable_register(frame, number) {
+            *register = value;
+        }
+    }
+    true
+}
+
+/// Handles `P<number>=<value>`.
+fn write_register_command(frame: &mut TrapFrame, arguments: &[u8]) -> bool {
+    let mut parts = arguments.splitn(2, |&byte| byte == b'=');
+    let number = match parts.next().and_then(parse_hex) {
+        Some(number) => number as usize,
+        None => return false,
+    };
+    match (writable_register(frame, number), parts.next()) {
+        (Some((register, size)), Some(digits)) => match parse_hex_le(digits, size) {
+            Some(value) => {
+                *register = value;
+                true
+            }
+            None => false,
+        },
+        // writes to the segment registers are accepted and ignored
+        (None, Some(_)) => number < REGISTER_COUNT,
+        _ => false,
+    }
+}
+
+/// Parses `<address>,<length>`, ignoring anything after a `:`.
+fn parse_address_and_length(arguments: &[u8]) -> Option<(u64, usize)> {
+    let arguments = arguments.split(|&byte| byte == b':').next()?;
+    let mut parts = arguments.splitn(2, |&byte| byte == b',');
+    let address = parse_hex(parts.next()?)?;
+    let length = parse_hex(parts.next()?)?;
+    Some((address, length as usize))
+}
+
+/// Checks the page table before touching memory, since a page fault inside
+/// the stub would stop the kernel for good.
+fn is_mapped(address: u64, length: usize) -> bool {
+    let end = match address.checked_add(length as u64) {
+        Some(end) => end,
+        None => return false,
+    };
+    let mut page = address & !0xfff;
+    while page < end {
+        if VirtAddr::try_new(page)
+            .ok()
+            .and_then(memory::page_flags)
+            .is_none()
+        {
+            return false;
+        }
+        page += 4096;
+    }
+    true
+}
+
+fn push_memory(reply: &mut Reply, address: u64, length: usize) -> bool {
+    if !is_mapped(address, length) {
+        return false;
+    }
+    for offset in 0..length as u64 {
+        let byte = unsafe { core::ptr::read_volatile((address + offset) as *const u8) };
+        reply.push_hex_byte(byte);
+    }
+    true
+}
+
+/// Handles `M<address>,<length>:<hex data>`.
+fn write_memory_command(arguments: &[u8]) -> bool {
+    let (address, length) = match parse_address_and_length(arguments) {
+        Some(parsed) => parsed,
+        None => return false,
+    };
+    let data = match arguments.iter().position(|&byte| byte == b':') {
+        Some(index) => &arguments[index + 1..],
+        None => return false,
+    };
+    if data.len() != length * 2 || !is_mapped(address, length) {
+        return false;
+    }
+    for (offset, pair) in data.chunks(2).enumerate() {
+        match parse_hex_byte(pair) {
+            Some(byte) => write_byte(address + offset as u64, byte),
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Writes to memory even if the page is read-only, e.g. to put breakpoints into code.
+fn write_byte(address: u64, byte: u8) {
+    use x86_64::registers::control::{Cr0, Cr0Flags};
+
+    let flags = Cr0::read();
+    unsafe {
+        Cr0::write(flags - Cr0Flags::WRITE_PROTECT);
+        core::ptr::write_volatile(address as *mut u8, byte);
+        Cr0::write(flags);
+    }
+}
+
+/// Inserts or removes a software breakpoint, returning whether that worked.
+fn set_breakpoint(breakpoints: &mut [Option<Breakpoint>], address: u64, insert: bool) -> bool {
+    let existing = breakpoints
+        .iter()
+        .position(|slot| matches!(slot, Some(breakpoint) if breakpoint.address == address));
+    match (insert, existing) {
+        (true, Some(_)) => true,
+        (true, None) => {
+            let slot = match breakpoints.iter().position(Option::is_none) {
+                Some(slot) => slot,
+                None => return false,
+            };
+            if !is_mapped(address, 1) {
+                return false;
+            }
+            let original = unsafe { core::ptr::read_volatile(address as *const u8) };
+            write_byte(address, INT3);
+            breakpoints[slot] = Some(Breakpoint { address, original });
+            true
+        }
+        (false, Some(slot)) => {
+            if let Some(breakpoint) = breakpoints[slot].take() {
+                write_byte(breakpoint.address, breakpoint.original);
+            }
+            true
+        }
+        (false, None) => false,
+    }
+}
@@ -0,0 +1,205 @@
+//! Framing of remote serial protocol packets: `$<data>#<two hex digit checksum>`.
+
+use crate::serial::Uart;
+
+/// Largest packet the stub accepts, announced to GDB in `qSupported`.
+pub const MAX_PACKET_SIZE: usize = 4096;
+
+const ACK: u8 = b'+';
+const NACK: u8 = b'-';
+const PACKET_START: u8 = b'$';
+const CHECKSUM_START: u8 = b'#';
+
+/// Sent by GDB outside of a packet to stop the target.
+pub const INTERRUPT: u8 = 0x03;
+
+pub struct Connection<'a> {
+    uart: &'a mut Uart,
+}
+
+impl<'a> Connection<'a> {
+    pub fn new(uart: &'a mut Uart) -> Self {
+        Connection { uart }
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        loop {
+            if let Some(byte) = self.uart.try_receive() {
+                return byte;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Waits for a packet with a valid checksum and returns its data.
+    ///
+    /// If `started` is set, the `$` of the first packet has already been read.
+    pub fn receive<'b>(&mut self, buffer: &'b mut [u8], started: bool) -> &'b [u8] {
+        let mut started = started;
+        loop {
+            if !started {
+                while self.read_byte() != PACKET_START {}
+            }
+            started = false;
+
+            let mut len = 0;
+            let mut checksum = 0u8;
+            let complete = loop {
+                match self.read_byte() {
+                    CHECKSUM_START => break true,
+                    // a new packet starts before this one was complete
+                    PACKET_START => break false,
+                    byte => {
+                        checksum = checksum.wrapping_add(byte);
+                        if len < buffer.len() {
+                            buffer[len] = byte;
+                        }
+                        len += 1;
+                    }
+                }
+            };
+            if !complete {
+                started = true;
+                continue;
+            }
+
+            let expected = [self.read_byte(), self.read_byte()];
+            if len <= buffer.len() && parse_hex_byte(&expected) == Some(checksum) {
+                self.uart.send(ACK);
+                return &buffer[..len];
+            }
+            self.uart.send(NACK);
+        }
+    }
+
+    /// Sends a packet and waits until GDB acknowledges it.
+    pub fn send(&mut self, data: &[u8]) {
+        loop {
+            self.uart.send(PACKET_START);
+            let mut checksum = 0u8;
+            for &byte in data {
+                self.uart.send(byte);
+                checksum = checksum.wrapping_add(byte);
+            }
+            self.uart.send(CHECKSUM_START);
+            self.uart.send(HEX_DIGITS[usize::from(checksum >> 4)]);
+            self.uart.send(HEX_DIGITS[usize::from(checksum & 0xf)]);
+
+            match self.read_byte() {
+                NACK => continue,
+                _ => return,
+            }
+        }
+    }
+}
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// Builds a reply in a fixed buffer, since the stub runs without the heap.
+pub struct Reply {
+    bytes: [u8; MAX_PACKET_SIZE],
+    len: usize,
+}
+
+impl Reply {
+    pub const fn new() -> Reply {
+        Reply {
+            bytes: [0; MAX_PACKET_SIZE],
+            len: 0,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn push_str(&mut self, s: &str) {
+        for &byte in s.as_bytes() {
+            self.push(byte);
+        }
+    }
+
+    pub fn push_hex_byte(&mut self, byte: u8) {
+        self.push(HEX_DIGITS[usize::from(byte >> 4)]);
+        self.push(HEX_DIGITS[usize::from(byte & 0xf)]);
+    }
+
+    /// Appends the number in big endian hex, as used for addresses and sizes.
+    pub fn push_hex(&mut self, value: u64) {
+        let digits = ((64 - value.leading_zeros() as usize) + 3) / 4;
+        for index in (0..digits.max(1)).rev() {
+            self.push(HEX_DIGITS[(value >> (index * 4)) as usize & 0xf]);
+        }
+    }
+
+    /// Appends the value as `size` bytes in target (little endian) order.
+    pub fn push_hex_le(&mut self, value: u64, size: usize) {
+        for &byte in value.to_le_bytes()[..size].iter() {
+            self.push_hex_byte(byte);
+        }
+    }
+
+    fn push(&mut self, byte: u8) {
+        if self.len < self.bytes.len() {
+            self.bytes[self.len] = byte;
+            self.len += 1;
+        }
+    }
+}
+
+fn hex_digit(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        _ => None,
+    }
+}
+
+pub fn parse_hex_byte(digits: &[u8]) -> Option<u8> {
+    match digits {
+        [high, low] => Some(hex_digit(*high)? << 4 | hex_digit(*low)?),
+        _ => None,
+    }
+}
+
+/// Parses a big endian hex number, as used for addresses and lengths.
+pub fn parse_hex(digits: &[u8]) -> Option<u64> {
+    if digits.is_empty() || digits.len() > 16 {
+        return None;
+    }
+    digits.iter().try_fold(0u64, |value, &digit| {
+        Some(value << 4 | u64::from(hex_digit(digit)?))
+    })
+}
+
+/// Parses a register value sent as `size` hex encoded bytes in little endian order.
+pub fn parse_hex_le(digits: &[u8], size: usize) -> Option<u64> {
+    if digits.len() != size * 2 {
+        return None;
+    }
+    let mut bytes = [0u8; 8];
+    for (byte, pair) in bytes.iter_mut().zip(digits.chunks(2)) {
+        *byte = parse_hex_byte(pair)?;
+    }
+    Some(u64::from_le_bytes(bytes))
+}
+
+#[test_case]
+fn test_hex_parsing() {
+    assert_eq!(parse_hex(b"ffff8000001a"), Some(0xffff_8000_001a));
+    assert_eq!(parse_hex(b"12g"), None);
+    assert_eq!(parse_hex_le(b"efbeadde", 4), Some(0xdead_beef));
+    assert_eq!(parse_hex_byte(b"7F"), Some(0x7f));
+
+    let mut reply = Reply::new();
+    reply.push_hex_le(0x1234, 2);
+    assert_eq!(reply.as_bytes(), b"3412");
+    reply.clear();
+    reply.push_hex(4096);
+    assert_eq!(reply.as_bytes(), b"1000");
+}
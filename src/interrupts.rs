@@ -1,20 +1,51 @@
-use crate::{color_code, colored_print, gdt, hlt_loop, print, println, Color};
+use crate::{
+    backtrace::{self, Backtrace},
+    color_code, colored_print, gdb, gdt, hlt_loop, println,
+    sync::IrqSafeMutex,
+    Color,
+};
+use core::mem;
+pub use exception::nmi_count;
+pub use irq::{register_irq, unregister_irq, IrqError, IrqHandler, IRQ_COUNT};
 use lazy_static::lazy_static;
 use pic8259_simple::ChainedPics;
-use spin::Mutex;
-use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode};
+pub use stats::{interrupt_count, interrupt_table, spurious_count, InterruptTable};
+pub use trap::TrapFrame;
+use x86_64::{
+    instructions::{interrupts, port::Port},
+    structures::idt::{
+        Entry, HandlerFunc, InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode,
+    },
+};
+
+pub mod apic;
+mod exception;
+mod irq;
+mod stats;
+mod trap;
 
 pub const PIC_1_OFFSET: u8 = 32;
 pub const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
 
-pub static PICS: Mutex<ChainedPics> =
-    Mutex::new(unsafe { ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET) });
+const PIC_1_COMMAND: u16 = 0x20;
+const PIC_1_DATA: u16 = 0x21;
+const PIC_2_COMMAND: u16 = 0xa0;
+const PIC_2_DATA: u16 = 0xa1;
+const PIC_READ_IRR: u8 = 0x0a;
+const PIC_READ_ISR: u8 = 0x0b;
+const PIC_EOI: u8 = 0x20;
+
+pub static PICS: IrqSafeMutex<ChainedPics> =
+    IrqSafeMutex::new(unsafe { ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET) });
 
+/// Vectors of the ISA IRQs, which are the same behind the PICs and the I/O APICs.
 #[derive(Debug, Clone, Copy)]
 #[repr(u8)]
 pub enum InterruptIndex {
     Timer = PIC_1_OFFSET,
     Keyboard,
+    Com2 = PIC_1_OFFSET + 3,
+    Com1 = PIC_1_OFFSET + 4,
 }
 
 impl InterruptIndex {
@@ -25,33 +56,191 @@ impl InterruptIndex {
     fn as_usize(self) -> usize {
         usize::from(self.as_u8())
     }
+
+    /// The ISA IRQ line of the interrupt.
+    pub fn irq(self) -> u8 {
+        self.as_u8() - PIC_1_OFFSET
+    }
+}
+
+/// Acknowledges the IRQ being handled to whichever controller delivered it.
+fn end_of_interrupt(irq: u8) {
+    if apic::is_enabled() {
+        apic::end_of_interrupt();
+    } else {
+        unsafe { PICS.lock().notify_end_of_interrupt(PIC_1_OFFSET + irq) };
+    }
+}
+
+/// Whether a PIC raised `irq` without a device asking for it.
+///
+/// A PIC that loses an IRQ before the CPU acknowledges it reports its lowest
+/// priority one, IRQ 7 or 15, without setting its in-service bit. Such an IRQ must
+/// not be acknowledged, except that the master did see the slave's IRQ 15 on the
+/// cascade, so it gets its end of interrupt here.
+fn is_spurious(irq: u8) -> bool {
+    if apic::is_enabled() || (irq != 7 && irq != 15) {
+        return false;
+    }
+    if pic_in_service() & 1 << irq != 0 {
+        return false;
+    }
+    if irq == 15 {
+        unsafe { Port::<u8>::new(PIC_1_COMMAND).write(PIC_EOI) };
+    }
+    true
+}
+
+/// The IRQs the PICs are currently handling, one bit each.
+fn pic_in_service() -> u16 {
+    let mut master = Port::<u8>::new(PIC_1_COMMAND);
+    let mut slave = Port::<u8>::new(PIC_2_COMMAND);
+    unsafe {
+        master.write(PIC_READ_ISR);
+        slave.write(PIC_READ_ISR);
+        let in_service = u16::from_le_bytes([master.read(), slave.read()]);
+        master.write(PIC_READ_IRR);
+        slave.write(PIC_READ_IRR);
+        in_service
+    }
+}
+
+/// Masks or unmasks an ISA IRQ at whichever controller is in charge.
+pub fn set_irq_masked(irq: u8, masked: bool) {
+    interrupts::without_interrupts(|| {
+        if apic::is_enabled() {
+            apic::set_irq_masked(irq, masked);
+        } else {
+            set_pic_masked(irq, masked);
+        }
+    });
+}
+
+fn set_pic_masked(irq: u8, masked: bool) {
+    let (port, bit) = if irq < 8 {
+        (PIC_1_DATA, irq)
+    } else {
+        (PIC_2_DATA, irq - 8)
+    };
+    let mut port = Port::<u8>::new(port);
+    unsafe {
+        let mask = port.read();
+        port.write(if masked {
+            mask | 1 << bit
+        } else {
+            mask & !(1 << bit)
+        });
+    }
+}
+
+/// The IRQs masked at the PICs, one bit each.
+fn pic_masks() -> u16 {
+    unsafe {
+        let master = Port::<u8>::new(PIC_1_DATA).read();
+        let slave = Port::<u8>::new(PIC_2_DATA).read();
+        u16::from_le_bytes([master, slave])
+    }
 }
 
 lazy_static! {
     static ref IDT: InterruptDescriptorTable = {
         let mut idt = InterruptDescriptorTable::new();
-        idt.breakpoint.set_handler_fn(breakpoint_handler);
+        set_exception_handlers(&mut idt);
         unsafe {
             idt.double_fault
                 .set_handler_fn(double_fault_handler)
                 .set_stack_index(gdt::DOUBLE_FAULT_IST_INDEX);
         }
-        idt[InterruptIndex::Timer.as_usize()].set_handler_fn(timer_interrupt_handler);
-        idt[InterruptIndex::Keyboard.as_usize()].set_handler_fn(keyboard_interrupt_handler);
+        for (irq, &stub) in irq::STUBS.iter().enumerate() {
+            idt[usize::from(PIC_1_OFFSET) + irq].set_handler_fn(stub);
+        }
+        // GDB needs the registers of the code COM2 interrupts
+        idt[InterruptIndex::Com2.as_usize()].set_handler_fn(trap::handler(trap::trap_com2));
+        idt[usize::from(apic::SPURIOUS_VECTOR)].set_handler_fn(apic_spurious_handler);
         idt.page_fault.set_handler_fn(page_fault_handler);
         idt
     };
 }
 
+fn set_exception_handlers(idt: &mut InterruptDescriptorTable) {
+    use trap::{diverging_handler, handler, handler_with_error_code};
+
+    idt.divide_error
+        .set_handler_fn(handler(trap::trap_divide_error));
+    idt.debug.set_handler_fn(handler(trap::trap_debug));
+    idt.non_maskable_interrupt
+        .set_handler_fn(handler(trap::trap_non_maskable_interrupt));
+    idt.breakpoint
+        .set_handler_fn(handler(trap::trap_breakpoint));
+    idt.overflow.set_handler_fn(handler(trap::trap_overflow));
+    idt.bound_range_exceeded
+        .set_handler_fn(handler(trap::trap_bound_range_exceeded));
+    idt.invalid_opcode
+        .set_handler_fn(handler(trap::trap_invalid_opcode));
+    idt.device_not_available
+        .set_handler_fn(handler(trap::trap_device_not_available));
+    idt.invalid_tss
+        .set_handler_fn(handler_with_error_code(trap::trap_invalid_tss));
+    idt.segment_not_present
+        .set_handler_fn(handler_with_error_code(trap::trap_segment_not_present));
+    idt.stack_segment_fault
+        .set_handler_fn(handler_with_error_code(trap::trap_stack_segment_fault));
+    idt.general_protection_fault
+        .set_handler_fn(handler_with_error_code(trap::trap_general_protection_fault));
+    idt.x87_floating_point
+        .set_handler_fn(handler(trap::trap_x87_floating_point));
+    idt.alignment_check
+        .set_handler_fn(handler_with_error_code(trap::trap_alignment_check));
+    idt.machine_check
+        .set_handler_fn(diverging_handler(trap::trap_machine_check));
+    idt.simd_floating_point
+        .set_handler_fn(handler(trap::trap_simd_floating_point));
+    idt.virtualization
+        .set_handler_fn(handler(trap::trap_virtualization));
+
+    // x86_64 0.13 keeps vector 21 among its reserved entries, so it is set through
+    // the raw table, which has the same layout (checked by `_IDT_LAYOUT`)
+    let entries =
+        unsafe { &mut *(idt as *mut InterruptDescriptorTable as *mut [Entry<HandlerFunc>; 256]) };
+    entries[21].set_handler_fn(handler(trap::trap_control_protection));
+}
+
+/// Fails to compile if the IDT stops being 256 entries, which the reinterpretation
+/// for vector 21 relies on.
+const _IDT_LAYOUT: [(); mem::size_of::<InterruptDescriptorTable>()] =
+    [(); mem::size_of::<[Entry<HandlerFunc>; 256]>()];
+
 pub fn init_idt() {
     IDT.load();
 }
 
-extern "x86-interrupt" fn breakpoint_handler(stack_frame: &mut InterruptStackFrame) {
+/// Remaps the PICs past the exceptions and masks every IRQ until it gets a handler.
+pub fn init_pics() {
+    interrupts::without_interrupts(|| {
+        unsafe { PICS.lock().initialize() };
+        for irq in 0..IRQ_COUNT {
+            // the cascade has to stay open for the slave's IRQs to get through
+            set_pic_masked(irq, irq != 2);
+        }
+    });
+}
+
+fn debug_handler(frame: &mut TrapFrame) {
+    if gdb::is_enabled() {
+        return gdb::handle_trap(frame);
+    }
+    colored_print!(color_code!(Color::Red), "EXCEPTION: DEBUG\n{:#?}", frame);
+    println!();
+}
+
+fn breakpoint_handler(frame: &mut TrapFrame) {
+    if gdb::is_enabled() {
+        return gdb::handle_trap(frame);
+    }
     colored_print!(
         color_code!(Color::Red),
         "EXCEPTION: BREAKPOINT\n{:#?}",
-        stack_frame
+        frame
     );
     println!();
 }
@@ -60,39 +249,35 @@ extern "x86-interrupt" fn double_fault_handler(
     stack_frame: &mut InterruptStackFrame,
     _error_code: u64,
 ) -> ! {
+    stats::count(8);
+    backtrace::record_fault(Backtrace::from_interrupt(stack_frame));
     panic!("EXCEPTION: DOUBLE FAULT\n{:#?}", stack_frame);
 }
 
-extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: &mut InterruptStackFrame) {
-    print!(".");
-
-    unsafe {
-        PICS.lock()
-            .notify_end_of_interrupt(InterruptIndex::Timer.as_u8());
-    }
+extern "x86-interrupt" fn apic_spurious_handler(_stack_frame: &mut InterruptStackFrame) {
+    stats::count_spurious();
 }
 
-extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: &mut InterruptStackFrame) {
-    use crate::task;
-    use x86_64::instructions::port::Port;
-
-    let mut port = Port::new(0x60);
-    let scancode: u8 = unsafe { port.read() };
-
-    task::keyboard::add_scancode(scancode);
+/// COM2 and COM4 share IRQ3.
+fn com2_interrupt_handler(frame: &mut TrapFrame) {
+    let irq = InterruptIndex::Com2.irq();
+    gdb::handle_interrupt(frame);
+    irq::run_handler(irq);
 
-    unsafe {
-        PICS.lock()
-            .notify_end_of_interrupt(InterruptIndex::Keyboard.as_u8());
-    }
+    end_of_interrupt(irq);
 }
 
 extern "x86-interrupt" fn page_fault_handler(
     stack_frame: &mut InterruptStackFrame,
     error_code: PageFaultErrorCode,
 ) {
+    use crate::memory;
     use x86_64::registers::control::Cr2;
 
+    stats::count(14);
+    if memory::handle_page_fault(Cr2::read(), error_code) {
+        return;
+    }
     colored_print!(
         color_code!(Color::Red),
         "EXCEPTION: PAGE FAULT\n\
@@ -103,6 +288,7 @@ extern "x86-interrupt" fn page_fault_handler(
         error_code,
         stack_frame
     );
+    println!("{}", Backtrace::from_interrupt(stack_frame));
     hlt_loop();
 }
 
@@ -0,0 +1,385 @@
+//! The local APIC and the I/O APICs, which take over from the 8259 PICs when the
+//! MADT describes them.
+//!
+//! The ISA IRQs keep the vectors they have behind the PICs, so `InterruptIndex`
+//! works either way.
+
+use super::{pic_masks, PIC_1_OFFSET};
+use crate::{
+    acpi::{self, AcpiError, InterruptOverride, Polarity, TriggerMode},
+    sync::IrqSafeMutex,
+};
+use alloc::vec::Vec;
+use conquer_once::spin::OnceCell;
+use core::{
+    arch::x86_64::__cpuid,
+    ptr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use x86_64::{
+    instructions::{interrupts, port::Port},
+    registers::model_specific::Msr,
+    structures::paging::{FrameAllocator, Mapper, Page, PageTableFlags, PhysFrame, Size4KiB},
+    PhysAddr, VirtAddr,
+};
+
+/// Vector of the local APIC's spurious interrupts, which must not be acknowledged.
+pub const SPURIOUS_VECTOR: u8 = 0xff;
+
+/// Where the register pages are mapped: the local APIC's first, then one page for
+/// every I/O APIC.
+pub const MMIO_START: usize = 0x_6666_6666_0000;
+
+const IA32_APIC_BASE: u32 = 0x1b;
+const APIC_BASE_ENABLE: u64 = 1 << 11;
+const APIC_BASE_X2APIC: u64 = 1 << 10;
+const APIC_BASE_ADDRESS: u64 = 0x000f_ffff_ffff_f000;
+/// The x2APIC registers are MSRs from here on, one for every 16 bytes of xAPIC MMIO.
+const X2APIC_MSR_BASE: u32 = 0x800;
+
+const CPUID_EDX_APIC: u32 = 1 << 9;
+const CPUID_ECX_X2APIC: u32 = 1 << 21;
+
+// local APIC registers, by xAPIC offset
+const LAPIC_ID: u32 = 0x20;
+const LAPIC_TASK_PRIORITY: u32 = 0x80;
+const LAPIC_EOI: u32 = 0xb0;
+const LAPIC_SPURIOUS: u32 = 0xf0;
+const LAPIC_LVT_TIMER: u32 = 0x320;
+const LAPIC_LVT_ERROR: u32 = 0x370;
+const LAPIC_SOFTWARE_ENABLE: u32 = 1 << 8;
+const LVT_MASKED: u32 = 1 << 16;
+
+const IOAPIC_VERSION: u32 = 0x01;
+const IOAPIC_REDIRECTION_TABLE: u32 = 0x10;
+const REDIRECTION_ACTIVE_LOW: u64 = 1 << 13;
+const REDIRECTION_LEVEL: u64 = 1 << 15;
+const REDIRECTION_MASKED: u64 = 1 << 16;
+
+const ISA_IRQS: u8 = 16;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static LOCAL_APIC: OnceCell<LocalApic> = OnceCell::uninit();
+static IO_APICS: IrqSafeMutex<IoApics> = IrqSafeMutex::new(IoApics {
+    chips: Vec::new(),
+    isa_routes: [None; ISA_IRQS as usize],
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApicError {
+    /// The processor has no local APIC.
+    Unsupported,
+    NoIoApic,
+    /// Registers whose page could not be mapped.
+    MapFailed(PhysAddr),
+    AlreadyEnabled,
+    Acpi(AcpiError),
+}
+
+impl From<AcpiError> for ApicError {
+    fn from(error: AcpiError) -> Self {
+        ApicError::Acpi(error)
+    }
+}
+
+enum LocalApic {
+    XApic(VirtAddr),
+    X2Apic,
+}
+
+impl LocalApic {
+    unsafe fn read(&self, register: u32) -> u32 {
+        match *self {
+            LocalApic::XApic(base) => ptr::read_volatile((base + u64::from(register)).as_ptr()),
+            LocalApic::X2Apic => Msr::new(X2APIC_MSR_BASE + (register >> 4)).read() as u32,
+        }
+    }
+
+    unsafe fn write(&self, register: u32, value: u32) {
+        match *self {
+            LocalApic::XApic(base) => {
+                ptr::write_volatile((base + u64::from(register)).as_mut_ptr(), value)
+            }
+            LocalApic::X2Apic => {
+                Msr::new(X2APIC_MSR_BASE + (register >> 4)).write(u64::from(value))
+            }
+        }
+    }
+
+    fn id(&self) -> u32 {
+        let id = unsafe { self.read(LAPIC_ID) };
+        match self {
+            LocalApic::XApic(_) => id >> 24,
+            LocalApic::X2Apic => id,
+        }
+    }
+}
+
+struct IoApic {
+    base: VirtAddr,
+    gsi_base: u32,
+    inputs: u32,
+}
+
+impl IoApic {
+    unsafe fn read(&self, register: u32) -> u32 {
+        ptr::write_volatile(self.base.as_mut_ptr(), register);
+        ptr::read_volatile((self.base + 0x10u64).as_ptr())
+    }
+
+    unsafe fn write(&self, register: u32, value: u32) {
+        ptr::write_volatile(self.base.as_mut_ptr(), register);
+        ptr::write_volatile((self.base + 0x10u64).as_mut_ptr(), value);
+    }
+
+    fn set_redirection(&self, input: u32, entry: u64) {
+        let register = IOAPIC_REDIRECTION_TABLE + input * 2;
+        unsafe {
+            // mask first, so the entry is never live half written
+            self.write(register, REDIRECTION_MASKED as u32);
+            self.write(register + 1, (entry >> 32) as u32);
+            self.write(register, entry as u32);
+        }
+    }
+
+    fn redirection(&self, input: u32) -> u64 {
+        let register = IOAPIC_REDIRECTION_TABLE + input * 2;
+        unsafe { u64::from(self.read(register)) | u64::from(self.read(register + 1)) << 32 }
+    }
+}
+
+struct IoApics {
+    chips: Vec<IoApic>,
+    /// The chip and input each ISA IRQ is routed to.
+    isa_routes: [Option<(usize, u32)>; ISA_IRQS as usize],
+}
+
+/// Maps the register page at `address` to page `index` after `MMIO_START`, uncached,
+/// and returns where the registers are.
+///
+/// The complete physical memory mapping can't be used, as it is cached write-back.
+fn mmio(
+    address: PhysAddr,
+    index: usize,
+    mapper: &mut impl Mapper<Size4KiB>,
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>,
+) -> Result<VirtAddr, ApicError> {
+    let page = Page::containing_address(VirtAddr::new((MMIO_START + index * 4096) as u64));
+    let frame = PhysFrame::containing_address(address);
+    let flags = PageTableFlags::PRESENT
+        | PageTableFlags::WRITABLE
+        | PageTableFlags::NO_CACHE
+        | PageTableFlags::WRITE_THROUGH;
+    unsafe {
+        mapper
+            .map_to(page, frame, flags, frame_allocator)
+            .map_err(|_| ApicError::MapFailed(address))?
+            .flush();
+    }
+    Ok(page.start_address() + (address - frame.start_address()))
+}
+
+/// Enables the local APIC, in x2APIC mode if the processor has it, routes the ISA
+/// IRQs through the I/O APICs and masks the 8259 PICs.
+///
+/// The IRQs unmasked at the PICs stay unmasked at the I/O APICs.
+///
+/// On error the PICs stay in charge. Needs the heap and `acpi::init`.
+pub fn init(
+    mapper: &mut impl Mapper<Size4KiB>,
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>,
+) -> Result<(), ApicError> {
+    if is_enabled() {
+        return Err(ApicError::AlreadyEnabled);
+    }
+    let features = unsafe { __cpuid(1) };
+    if features.edx & CPUID_EDX_APIC == 0 {
+        return Err(ApicError::Unsupported);
+    }
+    let madt = acpi::madt()?;
+
+    let mut chips = Vec::new();
+    for (index, io_apic) in madt.io_apics.iter().enumerate() {
+        let base = mmio(io_apic.address, index + 1, mapper, frame_allocator)?;
+        let mut chip = IoApic {
+            base,
+            gsi_base: io_apic.gsi_base,
+            inputs: 0,
+        };
+        chip.inputs = ((unsafe { chip.read(IOAPIC_VERSION) } >> 16) & 0xff) + 1;
+        chips.push(chip);
+    }
+    if chips.is_empty() {
+        return Err(ApicError::NoIoApic);
+    }
+
+    let mut apic_base = Msr::new(IA32_APIC_BASE);
+    let base = unsafe { apic_base.read() };
+    let local_apic = if features.ecx & CPUID_ECX_X2APIC != 0 {
+        LocalApic::X2Apic
+    } else {
+        let address = PhysAddr::new(base & APIC_BASE_ADDRESS);
+        LocalApic::XApic(mmio(address, 0, mapper, frame_allocator)?)
+    };
+
+    interrupts::without_interrupts(|| {
+        unsafe {
+            // x2APIC mode can only be entered from xAPIC mode, not from disabled
+            apic_base.write(base | APIC_BASE_ENABLE);
+            if let LocalApic::X2Apic = local_apic {
+                apic_base.write(base | APIC_BASE_ENABLE | APIC_BASE_X2APIC);
+            }
+            local_apic.write(LAPIC_TASK_PRIORITY, 0);
+            local_apic.write(LAPIC_LVT_TIMER, LVT_MASKED);
+            local_apic.write(LAPIC_LVT_ERROR, LVT_MASKED);
+            local_apic.write(
+                LAPIC_SPURIOUS,
+                LAPIC_SOFTWARE_ENABLE | u32::from(SPURIOUS_VECTOR),
+            );
+        }
+        let destination = u64::from(local_apic.id()) << 56;
+
+        let mut io_apics = IO_APICS.lock();
+        for chip in &chips {
+            for input in 0..chip.inputs {
+                chip.set_redirection(input, REDIRECTION_MASKED);
+            }
+        }
+        for irq in 0..ISA_IRQS {
+            let (gsi, entry) = match isa_redirection(&madt.overrides, irq) {
+                Some(redirection) => redirection,
+                None => continue,
+            };
+            let chip_index = chips
+                .iter()
+                .position(|chip| (chip.gsi_base..chip.gsi_base + chip.inputs).contains(&gsi));
+            let chip_index = match chip_index {
+                Some(index) => index,
+                None => continue,
+            };
+            let input = gsi - chips[chip_index].gsi_base;
+            let entry = destination | entry;
+            chips[chip_index].set_redirection(input, entry);
+            io_apics.isa_routes[usize::from(irq)] = Some((chip_index, input));
+        }
+        io_apics.chips = chips;
+
+        let masks = pic_masks();
+        mask_pics();
+        LOCAL_APIC.init_once(|| local_apic);
+        ENABLED.store(true, Ordering::SeqCst);
+
+        for irq in (0..ISA_IRQS).filter(|irq| masks & 1 << irq == 0) {
+            set_route_masked(&io_apics, irq, false);
+        }
+    });
+    Ok(())
+}
+
+/// Returns the global system interrupt of an ISA IRQ and its masked redirection
+/// entry without a destination, or `None` if the IRQ must stay unrouted.
+fn isa_redirection(overrides: &[InterruptOverride], irq: u8) -> Option<(u32, u64)> {
+    let route = acpi::isa_irq(overrides, irq);
+    // IRQ 2 is the PIC cascade, and its input usually belongs to the PIT
+    let taken = overrides
+        .iter()
+        .any(|other| other.gsi == route.gsi && other.irq != irq);
+    if irq == 2 || taken {
+        return None;
+    }
+    let mut entry = REDIRECTION_MASKED | u64::from(PIC_1_OFFSET + irq);
+    if route.polarity == Polarity::ActiveLow {
+        entry |= REDIRECTION_ACTIVE_LOW;
+    }
+    if route.trigger_mode == TriggerMode::Level {
+        entry |= REDIRECTION_LEVEL;
+    }
+    Some((route.gsi, entry))
+}
+
+/// Masks every input of both 8259 PICs; their spurious interrupts may still arrive.
+fn mask_pics() {
+    unsafe {
+        Port::<u8>::new(0x21).write(0xff);
+        Port::<u8>::new(0xa1).write(0xff);
+    }
+}
+
+/// Masks or unmasks the I/O APIC input of an ISA IRQ.
+pub(super) fn set_irq_masked(irq: u8, masked: bool) {
+    set_route_masked(&IO_APICS.lock(), irq, masked);
+}
+
+fn set_route_masked(io_apics: &IoApics, irq: u8, masked: bool) {
+    if let Some(&Some((chip, input))) = io_apics.isa_routes.get(usize::from(irq)) {
+        let chip = &io_apics.chips[chip];
+        let entry = chip.redirection(input);
+        let entry = if masked {
+            entry | REDIRECTION_MASKED
+        } else {
+            entry & !REDIRECTION_MASKED
+        };
+        chip.set_redirection(input, entry);
+    }
+}
+
+/// Whether interrupts go through the APICs rather than the 8259 PICs.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// Whether the local APIC runs in x2APIC mode, with its registers in MSRs.
+pub fn is_x2apic() -> bool {
+    matches!(LOCAL_APIC.try_get().ok(), Some(LocalApic::X2Apic))
+}
+
+/// Returns the ID of this processor's local APIC, once it is enabled.
+pub fn local_apic_id() -> Option<u32> {
+    LOCAL_APIC.try_get().ok().map(LocalApic::id)
+}
+
+/// Signals the end of the interrupt being handled to the local APIC.
+pub(super) fn end_of_interrupt() {
+    if let Some(local_apic) = LOCAL_APIC.try_get().ok() {
+        unsafe { local_apic.write(LAPIC_EOI, 0) };
+    }
+}
+
+#[test_case]
+fn test_isa_redirection() {
+    let route = |irq, gsi, polarity, trigger_mode| InterruptOverride {
+        irq,
+        gsi,
+        polarity,
+        trigger_mode,
+    };
+    let overrides = [
+        route(0, 2, Polarity::ActiveHigh, TriggerMode::Edge),
+        route(5, 10, Polarity::ActiveHigh, TriggerMode::Edge),
+        route(9, 9, Polarity::ActiveLow, TriggerMode::Level),
+    ];
+    let vector = |irq: u8| u64::from(PIC_1_OFFSET + irq);
+
+    assert_eq!(
+        isa_redirection(&overrides, 0),
+        Some((2, REDIRECTION_MASKED | vector(0)))
+    );
+    assert_eq!(
+        isa_redirection(&overrides, 1),
+        Some((1, REDIRECTION_MASKED | vector(1)))
+    );
+    assert_eq!(isa_redirection(&overrides, 2), None);
+    assert_eq!(
+        isa_redirection(&overrides, 5),
+        Some((10, REDIRECTION_MASKED | vector(5)))
+    );
+    assert_eq!(
+        isa_redirection(&overrides, 9),
+        Some((
+            9,
+            REDIRECTION_MASKED | REDIRECTION_ACTIVE_LOW | REDIRECTION_LEVEL | vector(9)
+        ))
+    );
+    // IRQ 10 would land on the input that IRQ 5 was moved to
+    assert_eq!(isa_redirection(&overrides, 10), None);
+}
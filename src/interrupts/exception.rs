@@ -0,0 +1,205 @@
+//! Reports of the CPU exceptions the kernel can't recover from.
+
+use super::TrapFrame;
+use crate::{
+    backtrace::{self, Backtrace},
+    color_code, colored_print, Color,
+};
+use core::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+static NMIS: AtomicU64 = AtomicU64::new(0);
+
+/// Mnemonic and name of each architectural exception, by vector.
+const EXCEPTIONS: [Option<(&str, &str)>; 32] = [
+    Some(("#DE", "DIVIDE ERROR")),
+    Some(("#DB", "DEBUG")),
+    Some(("NMI", "NON-MASKABLE INTERRUPT")),
+    Some(("#BP", "BREAKPOINT")),
+    Some(("#OF", "OVERFLOW")),
+    Some(("#BR", "BOUND RANGE EXCEEDED")),
+    Some(("#UD", "INVALID OPCODE")),
+    Some(("#NM", "DEVICE NOT AVAILABLE")),
+    Some(("#DF", "DOUBLE FAULT")),
+    None,
+    Some(("#TS", "INVALID TSS")),
+    Some(("#NP", "SEGMENT NOT PRESENT")),
+    Some(("#SS", "STACK SEGMENT FAULT")),
+    Some(("#GP", "GENERAL PROTECTION FAULT")),
+    Some(("#PF", "PAGE FAULT")),
+    None,
+    Some(("#MF", "X87 FLOATING POINT")),
+    Some(("#AC", "ALIGNMENT CHECK")),
+    Some(("#MC", "MACHINE CHECK")),
+    Some(("#XM", "SIMD FLOATING POINT")),
+    Some(("#VE", "VIRTUALIZATION")),
+    Some(("#CP", "CONTROL PROTECTION")),
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+    Some(("#SX", "SECURITY EXCEPTION")),
+    None,
+];
+
+/// Returns the mnemonic and name of an exception vector.
+pub(super) fn describe(vector: u8) -> (&'static str, &'static str) {
+    EXCEPTIONS
+        .get(usize::from(vector))
+        .copied()
+        .flatten()
+        .unwrap_or(("#??", "RESERVED"))
+}
+
+/// Called for every exception without a handler of its own.
+///
+/// An NMI is counted and returned from; everything else is fatal.
+pub(super) fn handle(frame: &mut TrapFrame) {
+    let vector = frame.vector as u8;
+    // an NMI can arrive while the interrupted code holds the console lock
+    if vector == 2 {
+        NMIS.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+    let (mnemonic, name) = describe(vector);
+    colored_print!(
+        color_code!(Color::Red),
+        "EXCEPTION: {} {} (vector {})\n{}{}{}",
+        name,
+        mnemonic,
+        vector,
+        ErrorCode(vector, frame.error_code),
+        Registers(frame),
+        ControlRegisters::read()
+    );
+    backtrace::record_fault(Backtrace::from_fault(frame.rip, frame.rsp, frame.rbp));
+    panic!("EXCEPTION: {} {} at {:#x}", name, mnemonic, frame.rip);
+}
+
+/// Number of NMIs since boot.
+pub fn nmi_count() -> u64 {
+    NMIS.load(Ordering::Relaxed)
+}
+
+/// The error code of an exception, decoded for the vectors that have one.
+struct ErrorCode(u8, u64);
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ErrorCode(vector, code) = *self;
+        match vector {
+            10..=13 => {
+                write!(f, "Error Code: {:#x}", code)?;
+                if code == 0 {
+                    return writeln!(f, " (no selector)");
+                }
+                let table = match (code >> 1) & 0b11 {
+                    0b00 => "GDT",
+                    0b10 => "LDT",
+                    _ => "IDT",
+                };
+                writeln!(
+                    f,
+                    " ({} entry {}, selector {:#x}{})",
+                    table,
+                    (code >> 3) & 0x1fff,
+                    code & 0xfff8,
+                    if code & 1 != 0 { ", external" } else { "" }
+                )
+            }
+            21 => {
+                let kind = match code & 0x7fff {
+                    1 => "near RET",
+                    2 => "far RET or IRET",
+                    3 => "missing ENDBRANCH",
+                    4 => "RSTORSSP",
+                    5 => "SETSSBSY",
+                    _ => "unknown",
+                };
+                writeln!(f, "Error Code: {:#x} ({})", code, kind)
+            }
+            17 => writeln!(f, "Error Code: {:#x}", code),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The general purpose registers and the interrupt frame, two per line.
+struct Registers<'a>(&'a TrapFrame);
+
+impl fmt::Display for Registers<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let frame = self.0;
+        let registers = [
+            ("rip", frame.rip),
+            ("rsp", frame.rsp),
+            ("rflags", frame.rflags),
+            ("cs", frame.cs),
+            ("ss", frame.ss),
+            ("rbp", frame.rbp),
+            ("rax", frame.rax),
+            ("rbx", frame.rbx),
+            ("rcx", frame.rcx),
+            ("rdx", frame.rdx),
+            ("rsi", frame.rsi),
+            ("rdi", frame.rdi),
+            ("r8", frame.r8),
+            ("r9", frame.r9),
+            ("r10", frame.r10),
+            ("r11", frame.r11),
+            ("r12", frame.r12),
+            ("r13", frame.r13),
+            ("r14", frame.r14),
+            ("r15", frame.r15),
+        ];
+        for pair in registers.chunks(2) {
+            for &(name, value) in pair {
+                write!(f, "{:>6} {:#018x}  ", name, value)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+struct ControlRegisters {
+    cr0: u64,
+    cr2: u64,
+    cr3: u64,
+    cr4: u64,
+    efer: u64,
+}
+
+impl ControlRegisters {
+    fn read() -> ControlRegisters {
+        use x86_64::registers::{
+            control::{Cr0, Cr2, Cr3, Cr4},
+            model_specific::Efer,
+        };
+
+        let (level_4_table, cr3_flags) = Cr3::read();
+        ControlRegisters {
+            cr0: Cr0::read_raw(),
+            cr2: Cr2::read().as_u64(),
+            cr3: level_4_table.start_address().as_u64() | cr3_flags.bits(),
+            cr4: Cr4::read_raw(),
+            efer: Efer::read_raw(),
+        }
+    }
+}
+
+impl fmt::Display for ControlRegisters {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>6} {:#018x}  {:>6} {:#018x}\n{:>6} {:#018x}  {:>6} {:#018x}\n{:>6} {:#018x}",
+            "cr0", self.cr0, "cr2", self.cr2, "cr3", self.cr3, "cr4", self.cr4, "efer", self.efer
+        )
+    }
+}
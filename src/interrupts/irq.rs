@@ -0,0 +1,125 @@
+//! Entry points for the 16 ISA IRQs, which call the handlers drivers register at
+//! runtime and acknowledge the interrupt afterwards.
+
+use super::{end_of_interrupt, is_spurious, set_irq_masked, stats, PIC_1_OFFSET};
+use crate::sync::IrqSafeMutex;
+use x86_64::structures::idt::{HandlerFunc, InterruptStackFrame};
+
+pub const IRQ_COUNT: u8 = 16;
+
+/// The IRQ the slave PIC is chained to, which never fires on its own.
+const CASCADE_IRQ: u8 = 2;
+
+/// Runs in the interrupt handler, with interrupts disabled; gets the IRQ number.
+pub type IrqHandler = fn(irq: u8);
+
+static HANDLERS: IrqSafeMutex<[Option<IrqHandler>; IRQ_COUNT as usize]> =
+    IrqSafeMutex::new([None; IRQ_COUNT as usize]);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IrqError {
+    InvalidIrq(u8),
+    AlreadyRegistered(u8),
+    NotRegistered(u8),
+}
+
+macro_rules! irq_stubs {
+    ($($irq:literal => $stub:ident),* $(,)?) => {
+        $(
+            extern "x86-interrupt" fn $stub(_stack_frame: &mut InterruptStackFrame) {
+                dispatch($irq);
+            }
+        )*
+
+        /// Entry points of the IRQs, by IRQ number.
+        pub(super) const STUBS: [HandlerFunc; IRQ_COUNT as usize] = [$($stub),*];
+    };
+}
+
+irq_stubs! {
+    0 => irq_0,
+    1 => irq_1,
+    2 => irq_2,
+    3 => irq_3,
+    4 => irq_4,
+    5 => irq_5,
+    6 => irq_6,
+    7 => irq_7,
+    8 => irq_8,
+    9 => irq_9,
+    10 => irq_10,
+    11 => irq_11,
+    12 => irq_12,
+    13 => irq_13,
+    14 => irq_14,
+    15 => irq_15,
+}
+
+fn dispatch(irq: u8) {
+    if is_spurious(irq) {
+        stats::count_spurious();
+        return;
+    }
+    stats::count(PIC_1_OFFSET + irq);
+    run_handler(irq);
+    end_of_interrupt(irq);
+}
+
+/// Calls the handler registered for `irq`, if any.
+pub(super) fn run_handler(irq: u8) {
+    // copied out, so that the handler may register and unregister handlers itself
+    let handler = HANDLERS.lock()[usize::from(irq)];
+    if let Some(handler) = handler {
+        handler(irq);
+    }
+}
+
+/// Calls `handler` whenever `irq` fires and unmasks it.
+pub fn register_irq(irq: u8, handler: IrqHandler) -> Result<(), IrqError> {
+    if irq >= IRQ_COUNT || irq == CASCADE_IRQ {
+        return Err(IrqError::InvalidIrq(irq));
+    }
+    let mut handlers = HANDLERS.lock();
+    let slot = &mut handlers[usize::from(irq)];
+    if slot.is_some() {
+        return Err(IrqError::AlreadyRegistered(irq));
+    }
+    *slot = Some(handler);
+    set_irq_masked(irq, false);
+    Ok(())
+}
+
+/// Masks `irq` and removes its handler, which is returned.
+pub fn unregister_irq(irq: u8) -> Result<IrqHandler, IrqError> {
+    if irq >= IRQ_COUNT {
+        return Err(IrqError::InvalidIrq(irq));
+    }
+    let handler = HANDLERS.lock()[usize::from(irq)]
+        .take()
+        .ok_or(IrqError::NotRegistered(irq))?;
+    set_irq_masked(irq, true);
+    Ok(handler)
+}
+
+#[test_case]
+fn test_register_and_unregister_irq() {
+    fn handler(_irq: u8) {}
+
+    assert_eq!(
+        register_irq(CASCADE_IRQ, handler),
+        Err(IrqError::InvalidIrq(2))
+    );
+    assert_eq!(
+        register_irq(IRQ_COUNT, handler),
+        Err(IrqError::InvalidIrq(16))
+    );
+    assert!(register_irq(0, handler).is_err());
+
+    register_irq(5, handler).unwrap();
+    assert_eq!(
+        register_irq(5, handler),
+        Err(IrqError::AlreadyRegistered(5))
+    );
+    assert!(unregister_irq(5).is_ok());
+    assert_eq!(unregister_irq(5).err(), Some(IrqError::NotRegistered(5)));
+}
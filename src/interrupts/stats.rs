@@ -0,0 +1,122 @@
+//! How often each interrupt vector fired, in the spirit of `/proc/interrupts`.
+
+use super::{apic, exception, PIC_1_OFFSET};
+use core::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+const VECTORS: usize = 256;
+
+/// Conventional device of each ISA IRQ.
+const ISA_DEVICES: [&str; 16] = [
+    "timer",
+    "keyboard",
+    "cascade",
+    "COM2/COM4",
+    "COM1/COM3",
+    "LPT2",
+    "floppy",
+    "LPT1",
+    "RTC",
+    "ACPI",
+    "",
+    "",
+    "mouse",
+    "FPU",
+    "ATA primary",
+    "ATA secondary",
+];
+
+const ZERO: AtomicU64 = AtomicU64::new(0);
+static COUNTS: [AtomicU64; VECTORS] = [ZERO; VECTORS];
+static SPURIOUS: AtomicU64 = AtomicU64::new(0);
+
+/// Called by every interrupt handler with its vector.
+pub(super) fn count(vector: u8) {
+    COUNTS[usize::from(vector)].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Called for interrupts no device raised: spurious IRQ 7 and 15 from the PICs, and
+/// the local APIC's spurious vector. They are not counted for their vector.
+pub(super) fn count_spurious() {
+    SPURIOUS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// How often `vector` fired since boot.
+pub fn interrupt_count(vector: u8) -> u64 {
+    COUNTS[usize::from(vector)].load(Ordering::Relaxed)
+}
+
+/// How many spurious interrupts arrived since boot.
+pub fn spurious_count() -> u64 {
+    SPURIOUS.load(Ordering::Relaxed)
+}
+
+/// Takes a snapshot of the counters.
+pub fn interrupt_table() -> InterruptTable {
+    let mut counts = [0; VECTORS];
+    for (count, counter) in counts.iter_mut().zip(COUNTS.iter()) {
+        *count = counter.load(Ordering::Relaxed);
+    }
+    InterruptTable {
+        counts,
+        spurious: spurious_count(),
+        apic: apic::is_enabled(),
+    }
+}
+
+/// The vectors that fired at least once, with their counts and sources; displayed
+/// as one line per vector.
+pub struct InterruptTable {
+    counts: [u64; VECTORS],
+    spurious: u64,
+    apic: bool,
+}
+
+impl InterruptTable {
+    /// The vectors that fired, with their counts, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = (u8, u64)> + '_ {
+        (0..=u8::MAX)
+            .zip(self.counts.iter().copied())
+            .filter(|&(_, count)| count > 0)
+    }
+
+    pub fn spurious(&self) -> u64 {
+        self.spurious
+    }
+}
+
+impl fmt::Display for InterruptTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:>4} {:>12}  SOURCE", "VEC", "COUNT")?;
+        for (vector, count) in self.iter() {
+            write!(f, "{:>4} {:>12}  ", vector, count)?;
+            let irq = vector.wrapping_sub(PIC_1_OFFSET);
+            if vector < 32 {
+                let (mnemonic, name) = exception::describe(vector);
+                writeln!(f, "{:<8} {}", mnemonic, name)?;
+            } else if usize::from(irq) < ISA_DEVICES.len() {
+                let controller = if self.apic { "IO-APIC" } else { "XT-PIC" };
+                writeln!(
+                    f,
+                    "{:<8} IRQ {:<2} {}",
+                    controller,
+                    irq,
+                    ISA_DEVICES[usize::from(irq)]
+                )?;
+            } else {
+                writeln!(f, "other")?;
+            }
+        }
+        writeln!(f, "{:>4} {:>12}  spurious interrupts", "SPU", self.spurious)
+    }
+}
+
+#[test_case]
+fn test_breakpoint_is_counted() {
+    let before = interrupt_count(3);
+    x86_64::instructions::interrupts::int3();
+    assert_eq!(interrupt_count(3), before + 1);
+    assert!(interrupt_table().iter().any(|(vector, _)| vector == 3));
+}
@@ -0,0 +1,170 @@
+//! Assembly entry points for the vectors that need the complete register state,
+//! which the `x86-interrupt` calling convention does not expose.
+
+use super::exception;
+use core::mem;
+use x86_64::structures::idt::{DivergingHandlerFunc, HandlerFunc, HandlerFuncWithErrCode};
+
+/// Registers of the interrupted code, as saved by the trap stubs.
+///
+/// Changes to it are restored when the handler returns.
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct TrapFrame {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rbp: u64,
+    pub rdi: u64,
+    pub rsi: u64,
+    pub rdx: u64,
+    pub rcx: u64,
+    pub rbx: u64,
+    pub rax: u64,
+    pub vector: u64,
+    /// The error code the CPU pushed, or 0 for vectors without one.
+    pub error_code: u64,
+    pub rip: u64,
+    pub cs: u64,
+    pub rflags: u64,
+    pub rsp: u64,
+    pub ss: u64,
+}
+
+// Each stub pushes its vector, and a zero error code if the CPU doesn't push one, so
+// that every trap reaches `trap_common` with the same layout. Keep the vectors in sync
+// with `trap_dispatch`.
+global_asm!(
+    r#"
+.intel_syntax noprefix
+
+.macro trap_stub name, vector
+.global \name
+\name:
+    push 0
+    push \vector
+    jmp trap_common
+.endm
+
+.macro trap_stub_error_code name, vector
+.global \name
+\name:
+    push \vector
+    jmp trap_common
+.endm
+
+trap_common:
+    push rax
+    push rbx
+    push rcx
+    push rdx
+    push rsi
+    push rdi
+    push rbp
+    push r8
+    push r9
+    push r10
+    push r11
+    push r12
+    push r13
+    push r14
+    push r15
+    mov rdi, rsp
+    cld
+    call trap_dispatch
+    pop r15
+    pop r14
+    pop r13
+    pop r12
+    pop r11
+    pop r10
+    pop r9
+    pop r8
+    pop rbp
+    pop rdi
+    pop rsi
+    pop rdx
+    pop rcx
+    pop rbx
+    pop rax
+    add rsp, 16
+    iretq
+
+trap_stub trap_divide_error, 0
+trap_stub trap_debug, 1
+trap_stub trap_non_maskable_interrupt, 2
+trap_stub trap_breakpoint, 3
+trap_stub trap_overflow, 4
+trap_stub trap_bound_range_exceeded, 5
+trap_stub trap_invalid_opcode, 6
+trap_stub trap_device_not_available, 7
+trap_stub_error_code trap_invalid_tss, 10
+trap_stub_error_code trap_segment_not_present, 11
+trap_stub_error_code trap_stack_segment_fault, 12
+trap_stub_error_code trap_general_protection_fault, 13
+trap_stub trap_x87_floating_point, 16
+trap_stub_error_code trap_alignment_check, 17
+trap_stub trap_machine_check, 18
+trap_stub trap_simd_floating_point, 19
+trap_stub trap_virtualization, 20
+trap_stub_error_code trap_control_protection, 21
+trap_stub trap_com2, 35
+
+.att_syntax prefix
+"#
+);
+
+extern "C" {
+    pub fn trap_divide_error();
+    pub fn trap_debug();
+    pub fn trap_non_maskable_interrupt();
+    pub fn trap_breakpoint();
+    pub fn trap_overflow();
+    pub fn trap_bound_range_exceeded();
+    pub fn trap_invalid_opcode();
+    pub fn trap_device_not_available();
+    pub fn trap_invalid_tss();
+    pub fn trap_segment_not_present();
+    pub fn trap_stack_segment_fault();
+    pub fn trap_general_protection_fault();
+    pub fn trap_x87_floating_point();
+    pub fn trap_alignment_check();
+    pub fn trap_machine_check();
+    pub fn trap_simd_floating_point();
+    pub fn trap_virtualization();
+    pub fn trap_control_protection();
+    pub fn trap_com2();
+}
+
+/// Lets a trap stub be installed through the IDT API, which only takes handler functions.
+pub fn handler(stub: unsafe extern "C" fn()) -> HandlerFunc {
+    // the IDT entry only stores the address; the stub does its own `iretq`
+    unsafe { mem::transmute(stub as usize) }
+}
+
+/// Like `handler`, for the exceptions with an error code.
+pub fn handler_with_error_code(stub: unsafe extern "C" fn()) -> HandlerFuncWithErrCode {
+    unsafe { mem::transmute(stub as usize) }
+}
+
+/// Like `handler`, for the exceptions the IDT API expects not to return.
+pub fn diverging_handler(stub: unsafe extern "C" fn()) -> DivergingHandlerFunc {
+    unsafe { mem::transmute(stub as usize) }
+}
+
+#[no_mangle]
+extern "C" fn trap_dispatch(frame: &mut TrapFrame) {
+    super::stats::count(frame.vector as u8);
+    match frame.vector {
+        1 => super::debug_handler(frame),
+        3 => super::breakpoint_handler(frame),
+        35 => super::com2_interrupt_handler(frame),
+        0..=31 => exception::handle(frame),
+        vector => panic!("trap stub for unexpected vector {}", vector),
+    }
+}
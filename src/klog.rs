@@ -0,0 +1,234 @@
+use crate::{
+    color_code,
+    console::{AllConsoles, Console},
+    sync::IrqSafeMutex,
+    time, Color, ColorCode,
+};
+use core::fmt::Write;
+use lazy_static::lazy_static;
+use log::{Level, LevelFilter, Log, Metadata};
+use ring_buffer::{Record, RingBuffer};
+
+mod ring_buffer;
+
+/// Bytes of log records kept for `dmesg`.
+const LOG_BUFFER_SIZE: usize = 16 * 1024;
+
+const MAX_MODULE_FILTERS: usize = 8;
+
+static mut LOG_BYTES: [u8; LOG_BUFFER_SIZE] = [0; LOG_BUFFER_SIZE];
+
+lazy_static! {
+    static ref LOG_BUFFER: IrqSafeMutex<RingBuffer<'static>> =
+        IrqSafeMutex::new(RingBuffer::new(unsafe { &mut LOG_BYTES }));
+}
+
+static FILTERS: IrqSafeMutex<Filters> = IrqSafeMutex::new(Filters {
+    default: LevelFilter::Debug,
+    console: LevelFilter::Info,
+    modules: [None; MAX_MODULE_FILTERS],
+});
+
+static LOGGER: KernelLogger = KernelLogger;
+
+#[derive(Debug)]
+pub enum FilterError {
+    TooManyModules,
+}
+
+struct Filters {
+    /// Level of the records that are kept, unless a module filter says otherwise.
+    default: LevelFilter,
+    /// Level of the kept records that are also printed to the consoles.
+    console: LevelFilter,
+    modules: [Option<(&'static str, LevelFilter)>; MAX_MODULE_FILTERS],
+}
+
+impl Filters {
+    /// Returns the level of the most specific filter that covers `target`.
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .flatten()
+            .filter(|(module, _)| is_in_module(target, module))
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.default, |&(_, level)| level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .flatten()
+            .map(|&(_, level)| level)
+            .fold(self.default, LevelFilter::max)
+    }
+}
+
+/// Whether `target` is `module` or one of its submodules.
+fn is_in_module(target: &str, module: &str) -> bool {
+    match target.strip_prefix(module) {
+        Some(rest) => rest.is_empty() || rest.starts_with("::"),
+        None => false,
+    }
+}
+
+struct KernelLogger;
+
+impl Log for KernelLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= FILTERS.lock().level_for(metadata.target())
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let millis = time::uptime().as_millis();
+        let mut entry = Record::new();
+        entry.push_bytes(&[record.level() as u8]);
+        let _ = write!(
+            entry,
+            "[{:5}.{:03}] {:<5} {}: {}",
+            millis / 1000,
+            millis % 1000,
+            record.level(),
+            record.target(),
+            record.args()
+        );
+
+        LOG_BUFFER.lock().push(&entry);
+        let print = record.level() <= FILTERS.lock().console;
+        if print {
+            print_record(&AllConsoles, &entry);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Installs the kernel logger as the backend of the `log` macros.
+pub fn init() {
+    log::set_logger(&LOGGER).expect("kernel logger already installed");
+    update_max_level();
+}
+
+/// Sets the level of the records that are kept for modules without their own filter.
+pub fn set_level(level: LevelFilter) {
+    FILTERS.lock().default = level;
+    update_max_level();
+}
+
+/// Sets the level of the records that are kept for `module` and its submodules,
+/// e.g. `dv_os::task`.
+pub fn set_module_level(module: &'static str, level: LevelFilter) -> Result<(), FilterError> {
+    {
+        let mut filters = FILTERS.lock();
+        let slot = match filters
+            .modules
+            .iter()
+            .position(|filter| matches!(filter, Some((name, _)) if *name == module))
+        {
+            Some(index) => index,
+            None => filters
+                .modules
+                .iter()
+                .position(Option::is_none)
+                .ok_or(FilterError::TooManyModules)?,
+        };
+        filters.modules[slot] = Some((module, level));
+    }
+    update_max_level();
+    Ok(())
+}
+
+/// Sets the level of the records that are printed to the consoles as they are logged.
+pub fn set_console_level(level: LevelFilter) {
+    FILTERS.lock().console = level;
+}
+
+/// Lets the `log` macros skip formatting records that no filter would keep.
+fn update_max_level() {
+    let max_level = FILTERS.lock().max_level();
+    log::set_max_level(max_level);
+}
+
+/// Writes every record still in the ring buffer to `console`, oldest first.
+pub fn dmesg(console: &dyn Console) {
+    let mut record = Record::new();
+    let mut position = 0;
+    loop {
+        let next = {
+            let buffer = LOG_BUFFER.lock();
+            // skip whatever was overwritten since the last record was read
+            position = position.max(buffer.first());
+            buffer.read(position, &mut record)
+        };
+        match next {
+            Some(next) => position = next,
+            None => break,
+        }
+        print_record(console, &record);
+    }
+}
+
+fn print_record(console: &dyn Console, record: &Record) {
+    let (&level, text) = match record.as_bytes().split_first() {
+        Some(parts) => parts,
+        None => return,
+    };
+    // truncating a record may have cut a character in half
+    let text = match core::str::from_utf8(text) {
+        Ok(text) => text,
+        Err(error) => core::str::from_utf8(&text[..error.valid_up_to()]).unwrap_or_default(),
+    };
+    console.write(level_color(level), format_args!("{}\n", text));
+}
+
+fn level_color(level: u8) -> Option<ColorCode> {
+    match level {
+        level if level == Level::Error as u8 => Some(color_code!(Color::LightRed)),
+        level if level == Level::Warn as u8 => Some(color_code!(Color::Yellow)),
+        level if level == Level::Info as u8 => None,
+        level if level == Level::Debug as u8 => Some(color_code!(Color::LightGray)),
+        _ => Some(color_code!(Color::DarkGray)),
+    }
+}
+
+#[test_case]
+fn test_records_are_kept_in_ring_buffer() {
+    log::warn!("klog test {}", 42);
+
+    let mut record = Record::new();
+    let mut last = Record::new();
+    {
+        let buffer = LOG_BUFFER.lock();
+        let mut position = buffer.first();
+        while let Some(next) = buffer.read(position, &mut record) {
+            core::mem::swap(&mut record, &mut last);
+            position = next;
+        }
+    }
+    assert_eq!(last.as_bytes()[0], Level::Warn as u8);
+    assert!(last
+        .as_bytes()
+        .ends_with(b"WARN  dv_os::klog: klog test 42"));
+}
+
+#[test_case]
+fn test_module_filters() {
+    let mut filters = Filters {
+        default: LevelFilter::Info,
+        console: LevelFilter::Info,
+        modules: [None; MAX_MODULE_FILTERS],
+    };
+    filters.modules[0] = Some(("dv_os::task", LevelFilter::Trace));
+    filters.modules[1] = Some(("dv_os::task::keyboard", LevelFilter::Off));
+    assert_eq!(
+        filters.level_for("dv_os::task::executor"),
+        LevelFilter::Trace
+    );
+    assert_eq!(filters.level_for("dv_os::task::keyboard"), LevelFilter::Off);
+    assert_eq!(filters.level_for("dv_os::tasks"), LevelFilter::Info);
+    assert_eq!(filters.max_level(), LevelFilter::Trace);
+}
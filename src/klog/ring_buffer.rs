@@ -0,0 +1,139 @@
+/// Longest record that is kept; longer ones are cut off.
+pub const MAX_RECORD_LEN: usize = 256;
+
+const RECORD_SEPARATOR: u8 = b'\n';
+
+/// A byte ring buffer of newline terminated records that overwrites the oldest records
+/// when it is full.
+///
+/// Positions count every byte ever written, so a reader can tell when the record it was
+/// about to read has been overwritten in the meantime.
+pub struct RingBuffer<'a> {
+    bytes: &'a mut [u8],
+    written: u64,
+}
+
+/// A single record copied out of the ring buffer.
+pub struct Record {
+    bytes: [u8; MAX_RECORD_LEN],
+    len: usize,
+}
+
+impl Record {
+    pub const fn new() -> Record {
+        Record {
+            bytes: [0; MAX_RECORD_LEN],
+            len: 0,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    /// Appends as much of `bytes` as fits, leaving room for the separator.
+    pub fn push_bytes(&mut self, bytes: &[u8]) {
+        let count = bytes.len().min(MAX_RECORD_LEN - 1 - self.len);
+        self.bytes[self.len..self.len + count].copy_from_slice(&bytes[..count]);
+        self.len += count;
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl core::fmt::Write for Record {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        // separators inside a message would split it into several records
+        for byte in s.bytes() {
+            let byte = if byte == RECORD_SEPARATOR { b' ' } else { byte };
+            self.push_bytes(&[byte]);
+        }
+        Ok(())
+    }
+}
+
+impl<'a> RingBuffer<'a> {
+    pub fn new(bytes: &'a mut [u8]) -> Self {
+        RingBuffer { bytes, written: 0 }
+    }
+
+    fn size(&self) -> u64 {
+        self.bytes.len() as u64
+    }
+
+    pub fn push(&mut self, record: &Record) {
+        for &byte in record.as_bytes().iter().chain(&[RECORD_SEPARATOR]) {
+            let index = (self.written % self.size()) as usize;
+            self.bytes[index] = byte;
+            self.written += 1;
+        }
+    }
+
+    /// Position of the oldest complete record.
+    pub fn first(&self) -> u64 {
+        if self.written <= self.size() {
+            return 0;
+        }
+        // the oldest bytes may be the tail of a partly overwritten record
+        let mut position = self.written - self.size();
+        while position < self.written {
+            position += 1;
+            if self.byte(position - 1) == RECORD_SEPARATOR {
+                break;
+            }
+        }
+        position
+    }
+
+    /// Position just after the newest record.
+    pub fn end(&self) -> u64 {
+        self.written
+    }
+
+    /// Copies the record at `position` into `record` and returns the position of the next
+    /// one, or `None` if there is no record there anymore.
+    pub fn read(&self, position: u64, record: &mut Record) -> Option<u64> {
+        if position >= self.written || position + self.size() < self.written {
+            return None;
+        }
+        record.clear();
+        let mut position = position;
+        while position < self.written {
+            let byte = self.byte(position);
+            position += 1;
+            if byte == RECORD_SEPARATOR {
+                break;
+            }
+            record.push_bytes(&[byte]);
+        }
+        Some(position)
+    }
+
+    fn byte(&self, position: u64) -> u8 {
+        self.bytes[(position % self.size()) as usize]
+    }
+}
+
+#[test_case]
+fn test_ring_buffer_overwrites_oldest_records() {
+    use core::fmt::Write;
+
+    let mut bytes = [0; 16];
+    let mut ring = RingBuffer::new(&mut bytes);
+    let mut record = Record::new();
+    for message in ["first", "second", "third"].iter() {
+        record.clear();
+        record.write_str(message).unwrap();
+        ring.push(&record);
+    }
+
+    let mut position = ring.first();
+    position = ring.read(position, &mut record).unwrap();
+    assert_eq!(record.as_bytes(), b"second");
+    position = ring.read(position, &mut record).unwrap();
+    assert_eq!(record.as_bytes(), b"third");
+    assert_eq!(position, ring.end());
+    assert!(ring.read(0, &mut record).is_none());
+}
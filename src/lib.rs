@@ -3,9 +3,11 @@
 #![feature(abi_x86_interrupt)]
 #![feature(custom_test_frameworks)]
 #![feature(alloc_error_handler)]
+#![feature(asm)]
 #![feature(const_fn)]
 #![feature(const_in_array_repeat_expressions)]
 #![feature(const_mut_refs)]
+#![feature(global_asm)]
 #![feature(wake_trait)]
 #![test_runner(crate::test_runner)]
 #![reexport_test_harness_main = "test_main"]
@@ -13,12 +15,23 @@
 extern crate alloc;
 extern crate rlibc;
 
+pub mod acpi;
 pub mod allocator;
+pub mod backtrace;
+pub mod console;
+pub mod framebuffer;
+pub mod gdb;
 pub mod gdt;
 pub mod interrupts;
+pub mod klog;
 pub mod memory;
+pub mod pci;
+pub mod power;
 pub mod serial;
+pub mod sync;
 pub mod task;
+pub mod time;
+pub mod transfer;
 pub mod vga_buffer;
 
 use core::panic::PanicInfo;
@@ -31,9 +44,13 @@ use bootloader::{entry_point, BootInfo};
 entry_point!(test_kernel_main);
 
 pub fn init() {
+    klog::init();
     gdt::init();
     interrupts::init_idt();
-    unsafe { interrupts::PICS.lock().initialize() };
+    interrupts::init_pics();
+    serial::init();
+    time::init();
+    task::keyboard::init();
     x86_64::instructions::interrupts::enable();
 }
 
@@ -65,6 +82,8 @@ pub enum QemuExitCode {
     Failed = 0x11,
 }
 
+/// Ends a QEMU run with the isa-debug-exit device attached, as the tests do; see
+/// `power::shutdown` for turning off any other machine.
 pub fn exit_qemu(exit_code: QemuExitCode) {
     use x86_64::instructions::port::Port;
 
@@ -82,6 +101,8 @@ fn test_kernel_main(_boot_info: &'static BootInfo) -> ! {
 }
 
 pub fn test_runner(tests: &[&dyn Testable]) {
+    // keep the serial output to the test results
+    console::set_enabled(console::SERIAL, false);
     serial_println!("Running {} tests", tests.len());
     for test in tests {
         test.run();
@@ -92,6 +113,7 @@ pub fn test_runner(tests: &[&dyn Testable]) {
 pub fn test_panic_handler(info: &PanicInfo) -> ! {
     serial_println!("[failed]\n");
     serial_println!("Error: {}\n", info);
+    serial_println!("{}", backtrace::panic_backtrace());
     exit_qemu(QemuExitCode::Failed);
     hlt_loop();
 }
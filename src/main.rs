@@ -11,6 +11,14 @@ use core::panic::PanicInfo;
 
 entry_point!(kernel_main);
 
+/// Number of rows kept on the heap for Shift+PageUp/PageDown.
+#[cfg(not(test))]
+const SCROLLBACK_LINES: usize = 100;
+
+/// Resolution of the framebuffer console on machines with a Bochs graphics adapter.
+#[cfg(not(test))]
+const FRAMEBUFFER_SIZE: (u16, u16) = (1024, 768);
+
 #[cfg(not(test))]
 fn kernel_main(boot_info: &'static BootInfo) -> ! {
     // Initialization
@@ -18,7 +26,10 @@ fn kernel_main(boot_info: &'static BootInfo) -> ! {
 
     // Initializing heap allocator
     {
-        use dv_os::{allocator, memory, memory::BootInfoFrameAllocator};
+        use dv_os::{
+            acpi, allocator, framebuffer, interrupts, memory, memory::BootInfoFrameAllocator,
+            println, vga_buffer,
+        };
         use x86_64::VirtAddr;
 
         let phys_mem_offset = VirtAddr::new(boot_info.physical_memory_offset);
@@ -26,14 +37,42 @@ fn kernel_main(boot_info: &'static BootInfo) -> ! {
         let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
         allocator::init_heap(&mut mapper, &mut frame_allocator)
             .expect("heap initialization failed");
+        vga_buffer::init_scrollback(SCROLLBACK_LINES);
+
+        // Switching to a framebuffer console, if QEMU's standard VGA is there
+        let (width, height) = FRAMEBUFFER_SIZE;
+        if let Err(error) = framebuffer::init(width, height, &mut mapper, &mut frame_allocator) {
+            println!("Staying in text mode: {:?}", error);
+        }
+
+        // Reading the firmware's ACPI tables
+        if let Err(error) = acpi::init() {
+            log::warn!("ACPI tables not found: {:?}", error);
+        }
+
+        // Moving interrupt routing from the 8259 PICs to the APICs
+        if let Err(error) = interrupts::apic::init(&mut mapper, &mut frame_allocator) {
+            log::warn!("APIC setup failed, staying on the 8259 PICs: {:?}", error);
+        }
+
+        // Handing the mapper to the page fault handler, which backs lazy regions
+        memory::init_demand_paging(mapper, frame_allocator);
     }
 
     // Initialize task executor
     let mut executor = {
-        use dv_os::task::{executor::Executor, keyboard, Task};
+        use dv_os::task::{executor::Executor, keyboard, serial, status_bar, Task};
+        use dv_os::transfer;
 
         let mut executor = Executor::new();
         executor.spawn(Task::new(keyboard::print_keypresses()));
+        executor.spawn(Task::new(serial::print_serial_input()));
+        executor.spawn(Task::new(status_bar::update_status_bar()));
+        // Accept host transfers on COM3, if there is one
+        match transfer::init() {
+            Ok(()) => executor.spawn(Task::new(transfer::serve())),
+            Err(error) => log::info!("transfer protocol not started: {:?}", error),
+        }
         executor
     };
 
@@ -146,6 +185,11 @@ fn kernel_main(boot_info: &'static BootInfo) -> ! {
         executor.spawn(Task::new(example_task()));
     }
 
+    // Let GDB attach on COM2, if there is one
+    if let Err(error) = dv_os::gdb::init() {
+        log::info!("GDB stub not started: {:?}", error);
+    }
+
     // Running the task executor.
     executor.run();
 }
@@ -160,9 +204,10 @@ fn kernel_main(_boot_info: &'static BootInfo) -> ! {
 #[cfg(not(test))]
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    use dv_os::println;
+    use dv_os::{backtrace, println};
 
     println!("{}", info);
+    println!("{}", backtrace::panic_backtrace());
     dv_os::hlt_loop();
 }
 
@@ -1,9 +1,22 @@
 use bootloader::bootinfo::{MemoryMap, MemoryRegionType};
+use core::sync::atomic::{AtomicU64, Ordering};
 use x86_64::{
-    structures::paging::{FrameAllocator, OffsetPageTable, PageTable, PhysFrame, Size4KiB},
+    structures::paging::{
+        FrameAllocator, OffsetPageTable, PageTable, PageTableFlags, PhysFrame, Size4KiB,
+    },
     PhysAddr, VirtAddr,
 };
 
+pub(crate) use demand::handle_page_fault;
+pub use demand::{
+    init_demand_paging, register_lazy_region, unregister_lazy_region, LazyRegion, RegionError,
+};
+
+mod demand;
+
+/// Virtual address at which the bootloader mapped all of physical memory, or 0 before `init`.
+static PHYSICAL_MEMORY_OFFSET: AtomicU64 = AtomicU64::new(0);
+
 /// Initialize a new OffsetPageTable.
 ///
 /// # Safety
@@ -12,6 +25,7 @@ use x86_64::{
 /// to virtual memory at the passed `physical_memory_offset`.
 /// - Must be only called once to avoid aliasing `&mut` references.
 pub unsafe fn init(physical_memory_offset: VirtAddr) -> OffsetPageTable<'static> {
+    PHYSICAL_MEMORY_OFFSET.store(physical_memory_offset.as_u64(), Ordering::Relaxed);
     let level_4_table = active_level_4_table(physical_memory_offset);
     OffsetPageTable::new(level_4_table, physical_memory_offset)
 }
@@ -35,6 +49,56 @@ unsafe fn active_level_4_table(physical_memory_offset: VirtAddr) -> &'static mut
     &mut *page_table_ptr
 }
 
+/// Returns the virtual address of the complete physical memory mapping, once `init` was called.
+pub fn physical_memory_offset() -> Option<VirtAddr> {
+    match PHYSICAL_MEMORY_OFFSET.load(Ordering::Relaxed) {
+        0 => None,
+        offset => Some(VirtAddr::new(offset)),
+    }
+}
+
+/// Returns where physical address `addr` is mapped in the complete physical memory
+/// mapping, once `init` was called.
+pub fn phys_to_virt(addr: PhysAddr) -> Option<VirtAddr> {
+    physical_memory_offset().map(|offset| offset + addr.as_u64())
+}
+
+/// Returns the flags of the page that maps `addr` in the active page table, or `None`
+/// if it is not mapped.
+///
+/// `WRITABLE` and `USER_ACCESSIBLE` are only set if every level of the table allows them.
+pub fn page_flags(addr: VirtAddr) -> Option<PageTableFlags> {
+    use x86_64::registers::control::Cr3;
+
+    let physical_memory_offset = physical_memory_offset()?;
+    let indices = [
+        addr.p4_index(),
+        addr.p3_index(),
+        addr.p2_index(),
+        addr.p1_index(),
+    ];
+    let mut table_address = Cr3::read().0.start_address();
+    let mut permissions = PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE;
+    for (level, &index) in indices.iter().enumerate() {
+        let table_ptr: *const PageTable =
+            (physical_memory_offset + table_address.as_u64()).as_ptr();
+        let entry = unsafe { &(*table_ptr)[index] };
+        let flags = entry.flags();
+        if !flags.contains(PageTableFlags::PRESENT) {
+            return None;
+        }
+        permissions &= flags;
+        let is_leaf =
+            level == indices.len() - 1 || (level > 0 && flags.contains(PageTableFlags::HUGE_PAGE));
+        if is_leaf {
+            let access = PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE;
+            return Some((flags - access) | permissions);
+        }
+        table_address = entry.addr();
+    }
+    None
+}
+
 pub struct BootInfoFrameAllocator {
     memory_map: &'static MemoryMap,
     next: usize,
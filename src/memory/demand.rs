@@ -0,0 +1,173 @@
+//! Virtual regions whose pages are only backed by frames once they are touched.
+
+use super::BootInfoFrameAllocator;
+use crate::sync::IrqSafeMutex;
+use x86_64::{
+    structures::{
+        idt::PageFaultErrorCode,
+        paging::{FrameAllocator, Mapper, OffsetPageTable, Page, PageTableFlags, Size4KiB},
+    },
+    VirtAddr,
+};
+
+const MAX_LAZY_REGIONS: usize = 16;
+const PAGE_SIZE: u64 = 4096;
+
+/// The page table and frame allocator, handed over by `init_demand_paging`.
+static PAGING: IrqSafeMutex<Option<Paging>> = IrqSafeMutex::new(None);
+
+static REGIONS: IrqSafeMutex<[Option<LazyRegion>; MAX_LAZY_REGIONS]> =
+    IrqSafeMutex::new([None; MAX_LAZY_REGIONS]);
+
+struct Paging {
+    mapper: OffsetPageTable<'static>,
+    frame_allocator: BootInfoFrameAllocator,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionError {
+    /// `init_demand_paging` has not run yet.
+    NotInitialized,
+    /// Start and size must be multiples of the page size.
+    Unaligned,
+    Overlaps,
+    TooManyRegions,
+    NotFound,
+}
+
+/// A range of virtual memory that is mapped page by page on first access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LazyRegion {
+    pub start: VirtAddr,
+    pub size: u64,
+    /// Flags of the pages once they are mapped; `PRESENT` is implied.
+    pub flags: PageTableFlags,
+}
+
+impl LazyRegion {
+    fn contains(&self, addr: VirtAddr) -> bool {
+        addr >= self.start && addr - self.start < self.size
+    }
+
+    fn overlaps(&self, other: &LazyRegion) -> bool {
+        self.start < other.start + other.size && other.start < self.start + self.size
+    }
+}
+
+/// Keeps the page table and the frame allocator for resolving page faults.
+///
+/// Called once, after the eagerly mapped memory such as the heap is set up.
+pub fn init_demand_paging(
+    mapper: OffsetPageTable<'static>,
+    frame_allocator: BootInfoFrameAllocator,
+) {
+    *PAGING.lock() = Some(Paging {
+        mapper,
+        frame_allocator,
+    });
+}
+
+/// Reserves `size` bytes from `start` to be backed on demand.
+///
+/// The range must not be mapped already; its pages are zeroed when first touched.
+pub fn register_lazy_region(
+    start: VirtAddr,
+    size: u64,
+    flags: PageTableFlags,
+) -> Result<(), RegionError> {
+    if !start.is_aligned(PAGE_SIZE) || size % PAGE_SIZE != 0 || size == 0 {
+        return Err(RegionError::Unaligned);
+    }
+    let region = LazyRegion { start, size, flags };
+
+    if PAGING.lock().is_none() {
+        return Err(RegionError::NotInitialized);
+    }
+    let mut regions = REGIONS.lock();
+    if regions
+        .iter()
+        .flatten()
+        .any(|other| other.overlaps(&region))
+    {
+        return Err(RegionError::Overlaps);
+    }
+    let slot = regions
+        .iter_mut()
+        .find(|slot| slot.is_none())
+        .ok_or(RegionError::TooManyRegions)?;
+    *slot = Some(region);
+    Ok(())
+}
+
+/// Stops backing the region that starts at `start`; pages already mapped stay so.
+pub fn unregister_lazy_region(start: VirtAddr) -> Result<LazyRegion, RegionError> {
+    let mut regions = REGIONS.lock();
+    let slot = regions
+        .iter_mut()
+        .find(|slot| matches!(slot, Some(region) if region.start == start))
+        .ok_or(RegionError::NotFound)?;
+    Ok(slot.take().unwrap())
+}
+
+/// Maps a frame for a fault on a not yet touched page of a lazy region, returning
+/// whether the faulting instruction can be retried.
+///
+/// Called by the page fault handler.
+pub(crate) fn handle_page_fault(addr: VirtAddr, error_code: PageFaultErrorCode) -> bool {
+    if error_code.contains(PageFaultErrorCode::PROTECTION_VIOLATION) {
+        return false;
+    }
+    // a fault while one of the locks is held can't be resolved without deadlocking
+    let region = match REGIONS.try_lock() {
+        Some(regions) => match regions
+            .iter()
+            .flatten()
+            .find(|region| region.contains(addr))
+        {
+            Some(region) => *region,
+            None => return false,
+        },
+        None => return false,
+    };
+    if error_code.contains(PageFaultErrorCode::CAUSED_BY_WRITE)
+        && !region.flags.contains(PageTableFlags::WRITABLE)
+    {
+        return false;
+    }
+    let mut paging = match PAGING.try_lock() {
+        Some(paging) => paging,
+        None => return false,
+    };
+    let Paging {
+        mapper,
+        frame_allocator,
+    } = match paging.as_mut() {
+        Some(paging) => paging,
+        None => return false,
+    };
+
+    let frame = match frame_allocator.allocate_frame() {
+        Some(frame) => frame,
+        None => return false,
+    };
+    // zeroed through the physical memory mapping, as the page may be read-only
+    let contents = match super::phys_to_virt(frame.start_address()) {
+        Some(contents) => contents,
+        None => return false,
+    };
+    unsafe {
+        contents
+            .as_mut_ptr::<u8>()
+            .write_bytes(0, PAGE_SIZE as usize)
+    };
+
+    let page: Page<Size4KiB> = Page::containing_address(addr);
+    let flags = region.flags | PageTableFlags::PRESENT;
+    match unsafe { mapper.map_to(page, frame, flags, frame_allocator) } {
+        Ok(flush) => {
+            flush.flush();
+            true
+        }
+        Err(_) => false,
+    }
+}
@@ -0,0 +1,89 @@
+use x86_64::instructions::port::Port;
+
+const CONFIG_ADDRESS_PORT: u16 = 0xcf8;
+const CONFIG_DATA_PORT: u16 = 0xcfc;
+
+const VENDOR_NONE: u16 = 0xffff;
+const HEADER_TYPE_MULTI_FUNCTION: u32 = 0x80;
+
+/// A function on the PCI bus, addressed through configuration mechanism #1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PciDevice {
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+    pub vendor_id: u16,
+    pub device_id: u16,
+}
+
+impl PciDevice {
+    fn at(bus: u8, device: u8, function: u8) -> Option<PciDevice> {
+        let id = read_config(bus, device, function, 0x00);
+        let vendor_id = id as u16;
+        if vendor_id == VENDOR_NONE {
+            return None;
+        }
+        Some(PciDevice {
+            bus,
+            device,
+            function,
+            vendor_id,
+            device_id: (id >> 16) as u16,
+        })
+    }
+
+    pub fn read_config(&self, offset: u8) -> u32 {
+        read_config(self.bus, self.device, self.function, offset)
+    }
+
+    /// Returns the raw value of base address register `index` (0-5).
+    pub fn bar(&self, index: u8) -> u32 {
+        self.read_config(0x10 + index * 4)
+    }
+
+    /// Returns the address a memory space base address register points to.
+    pub fn memory_bar_address(&self, index: u8) -> u32 {
+        self.bar(index) & !0x0f
+    }
+}
+
+/// Reads a dword from the configuration space of the given function.
+pub fn read_config(bus: u8, device: u8, function: u8, offset: u8) -> u32 {
+    let address = 1 << 31
+        | u32::from(bus) << 16
+        | u32::from(device) << 11
+        | u32::from(function) << 8
+        | u32::from(offset & 0xfc);
+
+    let mut address_port = Port::new(CONFIG_ADDRESS_PORT);
+    let mut data_port = Port::new(CONFIG_DATA_PORT);
+    unsafe {
+        address_port.write(address);
+        data_port.read()
+    }
+}
+
+/// Scans every bus for the first function with the given vendor and device ID.
+pub fn find_device(vendor_id: u16, device_id: u16) -> Option<PciDevice> {
+    devices()
+        .find(|pci_device| pci_device.vendor_id == vendor_id && pci_device.device_id == device_id)
+}
+
+/// Iterates over every function present on the PCI bus.
+pub fn devices() -> impl Iterator<Item = PciDevice> {
+    (0..=255u8)
+        .flat_map(|bus| (0..32u8).map(move |device| (bus, device)))
+        .flat_map(|(bus, device)| {
+            let functions = match PciDevice::at(bus, device, 0) {
+                Some(_) if is_multi_function(bus, device) => 8,
+                Some(_) => 1,
+                None => 0,
+            };
+            (0..functions).filter_map(move |function| PciDevice::at(bus, device, function))
+        })
+}
+
+fn is_multi_function(bus: u8, device: u8) -> bool {
+    let header_type = read_config(bus, device, 0, 0x0c) >> 16;
+    header_type & HEADER_TYPE_MULTI_FUNCTION != 0
+}
@@ -0,0 +1,263 @@
+//! Turning the machine off and resetting it.
+
+use crate::{
+    acpi::{self, AcpiError, AddressSpace, Fadt, GenericAddress, Table},
+    memory,
+};
+use core::{convert::Infallible, ptr};
+use x86_64::{
+    instructions::{interrupts, port::Port},
+    PhysAddr, VirtAddr,
+};
+
+const SCI_ENABLE: u16 = 1 << 0;
+const SLEEP_TYPE_SHIFT: u16 = 10;
+const SLEEP_TYPE_MASK: u16 = 0b111 << SLEEP_TYPE_SHIFT;
+const SLEEP_ENABLE: u16 = 1 << 13;
+
+const AML_NAME_OP: u8 = 0x08;
+const AML_PACKAGE_OP: u8 = 0x12;
+const AML_ZERO_OP: u8 = 0x00;
+const AML_ONE_OP: u8 = 0x01;
+const AML_BYTE_PREFIX: u8 = 0x0a;
+
+const KEYBOARD_CONTROLLER_STATUS: u16 = 0x64;
+const KEYBOARD_CONTROLLER_INPUT_FULL: u8 = 1 << 1;
+const KEYBOARD_CONTROLLER_PULSE_RESET: u8 = 0xfe;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerError {
+    Acpi(AcpiError),
+    /// The DSDT has no `\_S5` object with the sleep types for soft off.
+    NoS5Object,
+    NoPm1ControlBlock,
+    UnsupportedAddressSpace(AddressSpace),
+    /// The firmware did not hand the power management hardware to the OS.
+    AcpiNotEnabled,
+    /// Soft off was requested, but the machine is still running.
+    StillRunning,
+}
+
+impl From<AcpiError> for PowerError {
+    fn from(error: AcpiError) -> Self {
+        PowerError::Acpi(error)
+    }
+}
+
+/// Turns the machine off through ACPI sleep state S5.
+///
+/// Only returns if that did not work. Needs `acpi::init`.
+pub fn shutdown() -> Result<Infallible, PowerError> {
+    let fadt = acpi::fadt()?;
+    let dsdt = Table::at(fadt.dsdt)?;
+    let (sleep_type_a, sleep_type_b) =
+        find_s5_sleep_types(dsdt.data()).ok_or(PowerError::NoS5Object)?;
+    let pm1a_control = fadt
+        .pm1a_control_block
+        .ok_or(PowerError::NoPm1ControlBlock)?;
+
+    let enabled = interrupts::are_enabled();
+    interrupts::disable();
+    let result = unsafe { enter_s5(fadt, &pm1a_control, sleep_type_a, sleep_type_b) };
+    if enabled {
+        interrupts::enable();
+    }
+    result
+}
+
+/// Resets the machine: through the FADT reset register, then by pulsing the reset
+/// line of the keyboard controller, and if both fail with a triple fault.
+pub fn reboot() -> ! {
+    interrupts::disable();
+
+    if let Ok(fadt) = acpi::fadt() {
+        if let Some(reset_register) = fadt.reset_register {
+            let result = unsafe { write_register(&reset_register, u64::from(fadt.reset_value)) };
+            if let Err(error) = result {
+                log::warn!("ACPI reset failed: {:?}", error);
+            }
+            delay(1_000_000);
+        }
+    }
+
+    unsafe {
+        let mut status = Port::<u8>::new(KEYBOARD_CONTROLLER_STATUS);
+        for _ in 0..100_000 {
+            if status.read() & KEYBOARD_CONTROLLER_INPUT_FULL == 0 {
+                break;
+            }
+        }
+        status.write(KEYBOARD_CONTROLLER_PULSE_RESET);
+    }
+    delay(1_000_000);
+
+    triple_fault();
+}
+
+/// Loads an empty IDT and raises an exception, which the CPU can't deliver.
+fn triple_fault() -> ! {
+    use x86_64::{instructions::tables::lidt, structures::DescriptorTablePointer};
+
+    let empty = DescriptorTablePointer { limit: 0, base: 0 };
+    unsafe { lidt(&empty) };
+    interrupts::int3();
+    unreachable!("the machine survived a triple fault");
+}
+
+/// Switches ACPI on if needed and writes the S5 sleep type to the PM1 control blocks.
+unsafe fn enter_s5(
+    fadt: &Fadt,
+    pm1a_control: &GenericAddress,
+    sleep_type_a: u8,
+    sleep_type_b: u8,
+) -> Result<Infallible, PowerError> {
+    let control = read_register(pm1a_control)? as u16;
+    if control & SCI_ENABLE == 0 {
+        enable_acpi(fadt.smi_command, fadt.acpi_enable, pm1a_control)?;
+    }
+
+    enter_sleep_state(pm1a_control, sleep_type_a)?;
+    if let Some(pm1b_control) = fadt.pm1b_control_block {
+        enter_sleep_state(&pm1b_control, sleep_type_b)?;
+    }
+
+    // the write takes a moment to take effect
+    delay(1_000_000);
+    Err(PowerError::StillRunning)
+}
+
+unsafe fn enter_sleep_state(
+    pm1_control: &GenericAddress,
+    sleep_type: u8,
+) -> Result<(), PowerError> {
+    let control = read_register(pm1_control)? as u16 & !SLEEP_TYPE_MASK;
+    let sleep_type = u16::from(sleep_type) << SLEEP_TYPE_SHIFT & SLEEP_TYPE_MASK;
+    write_register(pm1_control, u64::from(control | sleep_type | SLEEP_ENABLE))
+}
+
+/// Asks the firmware to hand over the power management hardware, and waits for it.
+unsafe fn enable_acpi(
+    smi_command: u32,
+    acpi_enable: u8,
+    pm1a_control: &GenericAddress,
+) -> Result<(), PowerError> {
+    if smi_command == 0 || acpi_enable == 0 {
+        return Err(PowerError::AcpiNotEnabled);
+    }
+    Port::<u8>::new(smi_command as u16).write(acpi_enable);
+    for _ in 0..1_000_000 {
+        if read_register(pm1a_control)? as u16 & SCI_ENABLE != 0 {
+            return Ok(());
+        }
+        core::hint::spin_loop();
+    }
+    Err(PowerError::AcpiNotEnabled)
+}
+
+/// Returns the sleep types of the `\_S5` package: `Name (_S5, Package () { a, b, ... })`.
+fn find_s5_sleep_types(aml: &[u8]) -> Option<(u8, u8)> {
+    let position = aml.windows(4).position(|name| name == b"_S5_")?;
+    // the name may have a root prefix
+    let before = aml[..position].iter().rev().take(2);
+    if !before.clone().any(|&byte| byte == AML_NAME_OP) {
+        return None;
+    }
+    let mut rest = aml.get(position + 4..)?;
+    if *rest.first()? != AML_PACKAGE_OP {
+        return None;
+    }
+    // the top two bits of the package length's lead byte count its extra bytes
+    let length_bytes = usize::from(*rest.get(1)? >> 6) + 1;
+    // skip the opcode, the package length and the element count
+    rest = rest.get(1 + length_bytes + 1..)?;
+
+    let (sleep_type_a, rest) = aml_integer(rest)?;
+    let (sleep_type_b, _) = aml_integer(rest).unwrap_or((0, rest));
+    Some((sleep_type_a, sleep_type_b))
+}
+
+/// Parses an AML integer small enough for a sleep type.
+fn aml_integer(aml: &[u8]) -> Option<(u8, &[u8])> {
+    match *aml.first()? {
+        AML_BYTE_PREFIX => Some((*aml.get(1)?, aml.get(2..)?)),
+        AML_ZERO_OP => Some((0, &aml[1..])),
+        AML_ONE_OP => Some((1, &aml[1..])),
+        // some firmware leaves out the prefix
+        value if value < 8 => Some((value, &aml[1..])),
+        _ => None,
+    }
+}
+
+/// Returns where a memory mapped register is in the complete physical memory mapping,
+/// once the page of each of its bytes is known to be mapped.
+fn register_address(register: &GenericAddress) -> Result<VirtAddr, PowerError> {
+    let is_mapped = |address: VirtAddr| memory::page_flags(address).is_some();
+    let last_byte = u64::from(register.bit_width.max(8) / 8 - 1);
+    memory::phys_to_virt(PhysAddr::new(register.address))
+        .filter(|&address| is_mapped(address) && is_mapped(address + last_byte))
+        .ok_or(PowerError::Acpi(AcpiError::MemoryNotMapped))
+}
+
+unsafe fn read_register(register: &GenericAddress) -> Result<u32, PowerError> {
+    match register.address_space {
+        AddressSpace::Io => {
+            let port = register.address as u16;
+            Ok(match register.bit_width {
+                8 => u32::from(Port::<u8>::new(port).read()),
+                32 => Port::<u32>::new(port).read(),
+                _ => u32::from(Port::<u16>::new(port).read()),
+            })
+        }
+        AddressSpace::Memory => {
+            let address = register_address(register)?;
+            Ok(match register.bit_width {
+                8 => u32::from(ptr::read_volatile(address.as_ptr::<u8>())),
+                32 => ptr::read_volatile(address.as_ptr::<u32>()),
+                _ => u32::from(ptr::read_volatile(address.as_ptr::<u16>())),
+            })
+        }
+        other => Err(PowerError::UnsupportedAddressSpace(other)),
+    }
+}
+
+unsafe fn write_register(register: &GenericAddress, value: u64) -> Result<(), PowerError> {
+    match register.address_space {
+        AddressSpace::Io => {
+            let port = register.address as u16;
+            match register.bit_width {
+                8 => Port::<u8>::new(port).write(value as u8),
+                32 => Port::<u32>::new(port).write(value as u32),
+                _ => Port::<u16>::new(port).write(value as u16),
+            }
+        }
+        AddressSpace::Memory => {
+            let address = register_address(register)?;
+            match register.bit_width {
+                8 => ptr::write_volatile(address.as_mut_ptr::<u8>(), value as u8),
+                32 => ptr::write_volatile(address.as_mut_ptr::<u32>(), value as u32),
+                _ => ptr::write_volatile(address.as_mut_ptr::<u16>(), value as u16),
+            }
+        }
+        other => return Err(PowerError::UnsupportedAddressSpace(other)),
+    }
+    Ok(())
+}
+
+fn delay(iterations: u32) {
+    for _ in 0..iterations {
+        core::hint::spin_loop();
+    }
+}
+
+#[test_case]
+fn test_find_s5_sleep_types() {
+    // Name (\_S5, Package (0x04) { 0x05, Zero, Zero, Zero })
+    let aml = [
+        0x10, 0x08, b'\\', b'_', b'S', b'5', b'_', 0x12, 0x08, 0x04, 0x0a, 0x05, 0x00, 0x00, 0x00,
+    ];
+    assert_eq!(find_s5_sleep_types(&aml), Some((5, 0)));
+    assert_eq!(find_s5_sleep_types(&aml[..7]), None);
+    // a method called _S5_ isn't the sleep state object
+    let method = [0x14, 0x05, b'_', b'S', b'5', b'_', 0x00];
+    assert_eq!(find_s5_sleep_types(&method), None);
+}
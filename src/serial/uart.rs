@@ -0,0 +1,240 @@
+use core::fmt;
+use x86_64::instructions::port::Port;
+
+/// Clock of the UART divided by 16, i.e. the baud rate with a divisor of 1.
+const MAX_BAUD_RATE: u32 = 115_200;
+
+// register offsets from the base port
+const DATA: u16 = 0;
+const INTERRUPT_ENABLE: u16 = 1;
+const FIFO_CONTROL: u16 = 2;
+const LINE_CONTROL: u16 = 3;
+const MODEM_CONTROL: u16 = 4;
+const LINE_STATUS: u16 = 5;
+
+/// Line control bit that maps the divisor latch over the data and interrupt enable registers.
+const DIVISOR_LATCH_ACCESS: u8 = 1 << 7;
+
+/// Enable and clear both FIFOs, interrupting once 14 bytes have been received.
+const FIFO_ENABLE_AND_CLEAR: u8 = 0xc7;
+
+/// DTR, RTS and OUT2, which connects the interrupt line to the PIC.
+const MODEM_NORMAL: u8 = 0x0b;
+/// RTS, OUT1, OUT2 and loopback, for checking that the UART exists.
+const MODEM_LOOPBACK: u8 = 0x1e;
+const LOOPBACK_TEST_BYTE: u8 = 0xae;
+
+const RECEIVED_DATA_INTERRUPT: u8 = 1 << 0;
+
+const DATA_READY: u8 = 1 << 0;
+const TRANSMITTER_EMPTY: u8 = 1 << 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataBits {
+    Five,
+    Six,
+    Seven,
+    Eight,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parity {
+    None,
+    Odd,
+    Even,
+    Mark,
+    Space,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopBits {
+    One,
+    /// Two stop bits, or one and a half with five data bits.
+    Two,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineSettings {
+    pub baud_rate: u32,
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+}
+
+impl LineSettings {
+    /// 38400 baud, 8 data bits, no parity and one stop bit.
+    pub const fn new() -> LineSettings {
+        LineSettings {
+            baud_rate: 38_400,
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+        }
+    }
+
+    fn divisor(&self) -> Result<u16, SerialError> {
+        if self.baud_rate == 0 || MAX_BAUD_RATE % self.baud_rate != 0 {
+            return Err(SerialError::UnsupportedBaudRate(self.baud_rate));
+        }
+        Ok((MAX_BAUD_RATE / self.baud_rate) as u16)
+    }
+
+    fn line_control(&self) -> u8 {
+        let data_bits = match self.data_bits {
+            DataBits::Five => 0b00,
+            DataBits::Six => 0b01,
+            DataBits::Seven => 0b10,
+            DataBits::Eight => 0b11,
+        };
+        let stop_bits = match self.stop_bits {
+            StopBits::One => 0,
+            StopBits::Two => 1 << 2,
+        };
+        let parity = match self.parity {
+            Parity::None => 0b000 << 3,
+            Parity::Odd => 0b001 << 3,
+            Parity::Even => 0b011 << 3,
+            Parity::Mark => 0b101 << 3,
+            Parity::Space => 0b111 << 3,
+        };
+        data_bits | stop_bits | parity
+    }
+}
+
+impl Default for LineSettings {
+    fn default() -> Self {
+        LineSettings::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerialError {
+    /// The baud rate does not divide 115200.
+    UnsupportedBaudRate(u32),
+    /// Nothing answered in the loopback test.
+    NotPresent,
+}
+
+/// A 16550 compatible UART.
+pub struct Uart {
+    base: u16,
+    settings: Option<LineSettings>,
+    /// Whether the last loopback test failed.
+    absent: bool,
+}
+
+impl Uart {
+    pub const fn new(base: u16) -> Uart {
+        Uart {
+            base,
+            settings: None,
+            absent: false,
+        }
+    }
+
+    /// Programs the line settings and checks that the UART exists.
+    pub fn init(&mut self, settings: LineSettings) -> Result<(), SerialError> {
+        let divisor = settings.divisor()?;
+        unsafe {
+            self.register(INTERRUPT_ENABLE).write(0);
+            self.register(LINE_CONTROL).write(DIVISOR_LATCH_ACCESS);
+            self.register(DATA).write(divisor as u8);
+            self.register(INTERRUPT_ENABLE).write((divisor >> 8) as u8);
+            self.register(LINE_CONTROL).write(settings.line_control());
+            self.register(FIFO_CONTROL).write(FIFO_ENABLE_AND_CLEAR);
+
+            self.register(MODEM_CONTROL).write(MODEM_LOOPBACK);
+            self.register(DATA).write(LOOPBACK_TEST_BYTE);
+            let echo = self.register(DATA).read();
+            self.register(MODEM_CONTROL).write(MODEM_NORMAL);
+            self.absent = echo != LOOPBACK_TEST_BYTE;
+            if self.absent {
+                return Err(SerialError::NotPresent);
+            }
+        }
+        self.settings = Some(settings);
+        Ok(())
+    }
+
+    /// The line settings, or `None` if the UART has not been initialized.
+    pub fn settings(&self) -> Option<LineSettings> {
+        self.settings
+    }
+
+    /// Whether `init` found nothing answering at the port's base.
+    pub fn is_absent(&self) -> bool {
+        self.absent
+    }
+
+    /// Raises the port's IRQ whenever a byte is received.
+    pub fn enable_receive_interrupt(&mut self) {
+        unsafe {
+            self.register(INTERRUPT_ENABLE)
+                .write(RECEIVED_DATA_INTERRUPT)
+        };
+    }
+
+    pub fn send(&mut self, byte: u8) {
+        unsafe {
+            while self.register(LINE_STATUS).read() & TRANSMITTER_EMPTY == 0 {
+                core::hint::spin_loop();
+            }
+            self.register(DATA).write(byte);
+        }
+    }
+
+    /// Returns the next received byte, if there is one.
+    pub fn try_receive(&mut self) -> Option<u8> {
+        receive(self.base)
+    }
+
+    fn register(&self, offset: u16) -> Port<u8> {
+        Port::new(self.base + offset)
+    }
+}
+
+/// Reads a received byte straight from the UART at `base`.
+///
+/// Only touches the receive side, so it does not need the lock of the `Uart`.
+pub(super) fn receive(base: u16) -> Option<u8> {
+    let mut line_status: Port<u8> = Port::new(base + LINE_STATUS);
+    let mut data: Port<u8> = Port::new(base + DATA);
+    unsafe {
+        if line_status.read() & DATA_READY != 0 {
+            Some(data.read())
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Write for Uart {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.send(byte);
+        }
+        Ok(())
+    }
+}
+
+#[test_case]
+fn test_line_settings_encoding() {
+    let settings = LineSettings {
+        baud_rate: 9600,
+        data_bits: DataBits::Seven,
+        parity: Parity::Even,
+        stop_bits: StopBits::Two,
+    };
+    assert_eq!(settings.divisor(), Ok(12));
+    assert_eq!(settings.line_control(), 0b0001_1110);
+    assert_eq!(LineSettings::new().line_control(), 0b0000_0011);
+
+    let settings = LineSettings {
+        baud_rate: 1000,
+        ..LineSettings::new()
+    };
+    assert_eq!(
+        settings.divisor(),
+        Err(SerialError::UnsupportedBaudRate(1000))
+    );
+}
@@ -0,0 +1,121 @@
+//! Locks for state that interrupt handlers share with the rest of the kernel.
+
+use core::{
+    fmt,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+};
+use spin::{Mutex, MutexGuard};
+use x86_64::instructions::interrupts;
+
+/// A spinlock that keeps interrupts disabled while it is held.
+///
+/// An interrupt handler taking a plain `spin::Mutex` the interrupted code holds spins
+/// forever; with this one the handler can't run until the lock is released. The guard
+/// restores the interrupt flag it found, so guards must be dropped in the reverse
+/// order of locking.
+pub struct IrqSafeMutex<T: ?Sized> {
+    inner: Mutex<T>,
+}
+
+impl<T> IrqSafeMutex<T> {
+    pub const fn new(value: T) -> Self {
+        IrqSafeMutex {
+            inner: Mutex::new(value),
+        }
+    }
+}
+
+impl<T: ?Sized> IrqSafeMutex<T> {
+    /// Disables interrupts and spins until the lock is free.
+    pub fn lock(&self) -> IrqSafeMutexGuard<T> {
+        let enabled = interrupts::are_enabled();
+        interrupts::disable();
+        IrqSafeMutexGuard {
+            guard: ManuallyDrop::new(self.inner.lock()),
+            enabled,
+        }
+    }
+
+    /// Takes the lock if it is free, for code that must not wait, like exception
+    /// handlers that may have interrupted the holder.
+    pub fn try_lock(&self) -> Option<IrqSafeMutexGuard<T>> {
+        let enabled = interrupts::are_enabled();
+        interrupts::disable();
+        match self.inner.try_lock() {
+            Some(guard) => Some(IrqSafeMutexGuard {
+                guard: ManuallyDrop::new(guard),
+                enabled,
+            }),
+            None => {
+                if enabled {
+                    interrupts::enable();
+                }
+                None
+            }
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.inner.is_locked()
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for IrqSafeMutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_lock() {
+            Some(guard) => write!(f, "IrqSafeMutex {{ data: {:?} }}", &*guard),
+            None => write!(f, "IrqSafeMutex {{ <locked> }}"),
+        }
+    }
+}
+
+pub struct IrqSafeMutexGuard<'a, T: ?Sized> {
+    guard: ManuallyDrop<MutexGuard<'a, T>>,
+    /// Whether interrupts were enabled before locking.
+    enabled: bool,
+}
+
+impl<T: ?Sized> Deref for IrqSafeMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T: ?Sized> DerefMut for IrqSafeMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T: ?Sized> Drop for IrqSafeMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        // unlocked first, so a pending interrupt can take the lock right away
+        unsafe { ManuallyDrop::drop(&mut self.guard) };
+        if self.enabled {
+            interrupts::enable();
+        }
+    }
+}
+
+#[test_case]
+fn test_guard_restores_interrupt_flag() {
+    let mutex = IrqSafeMutex::new(0);
+    assert!(interrupts::are_enabled());
+    {
+        let mut guard = mutex.lock();
+        assert!(!interrupts::are_enabled());
+        *guard += 1;
+        assert!(mutex.try_lock().is_none());
+        assert!(!interrupts::are_enabled());
+    }
+    assert!(interrupts::are_enabled());
+
+    interrupts::without_interrupts(|| {
+        drop(mutex.lock());
+        assert!(!interrupts::are_enabled());
+    });
+    assert_eq!(*mutex.lock(), 1);
+}
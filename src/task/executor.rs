@@ -1,8 +1,18 @@
 use super::{Task, TaskId};
 use alloc::{collections::BTreeMap, sync::Arc, task::Wake};
-use core::task::{Context, Poll, Waker};
+use core::{
+    sync::atomic::{AtomicUsize, Ordering},
+    task::{Context, Poll, Waker},
+};
 use crossbeam_queue::ArrayQueue;
 
+static TASK_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of spawned tasks that have not completed yet.
+pub fn task_count() -> usize {
+    TASK_COUNT.load(Ordering::Relaxed)
+}
+
 struct TaskWaker {
     task_id: TaskId,
     task_queue: Arc<ArrayQueue<TaskId>>,
@@ -51,6 +61,7 @@ impl Executor {
         if self.tasks.insert(task.id, task).is_some() {
             panic!("task with same ID already in tasks");
         }
+        TASK_COUNT.fetch_add(1, Ordering::Relaxed);
         self.task_queue.push(task_id).expect("queue full");
     }
 
@@ -81,6 +92,7 @@ impl Executor {
                 Poll::Ready(()) => {
                     tasks.remove(&task_id);
                     waker_cache.remove(&task_id);
+                    TASK_COUNT.fetch_sub(1, Ordering::Relaxed);
                 }
                 Poll::Pending => {}
             }
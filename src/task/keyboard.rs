@@ -1,6 +1,11 @@
-use crate::{print, println};
+use crate::{
+    console_print,
+    interrupts::{self, InterruptIndex},
+    vga_buffer,
+};
 use conquer_once::spin::OnceCell;
 use core::{
+    fmt,
     pin::Pin,
     task::{Context, Poll},
 };
@@ -9,7 +14,10 @@ use futures_util::{
     stream::{Stream, StreamExt},
     task::AtomicWaker,
 };
-use pc_keyboard::{layouts, DecodedKey, HandleControl, Keyboard, ScancodeSet1};
+use pc_keyboard::{
+    layouts, DecodedKey, HandleControl, KeyCode, KeyEvent, KeyState, Keyboard, ScancodeSet1,
+};
+use spin::Mutex;
 
 static SCANCODE_QUEUE: OnceCell<ArrayQueue<u8>> = OnceCell::uninit();
 static WAKER: AtomicWaker = AtomicWaker::new();
@@ -50,28 +58,176 @@ impl Stream for ScancodeStream {
     }
 }
 
+/// Reads the scancodes of IRQ1 into the `ScancodeStream`.
+pub fn init() {
+    interrupts::register_irq(InterruptIndex::Keyboard.irq(), keyboard_interrupt)
+        .expect("keyboard IRQ already taken");
+}
+
+fn keyboard_interrupt(_irq: u8) {
+    use x86_64::instructions::port::Port;
+
+    let mut port = Port::new(0x60);
+    let scancode: u8 = unsafe { port.read() };
+    add_scancode(scancode);
+}
+
 pub(crate) fn add_scancode(scancode: u8) {
     if let Ok(queue) = SCANCODE_QUEUE.try_get() {
         if queue.push(scancode).is_err() {
-            println!("WARNING: scancode queue full; dropping keyboard input");
+            log::warn!("scancode queue full; dropping keyboard input");
         } else {
             WAKER.wake();
         }
     } else {
-        println!("WARNING: scancode queue uninitialized");
+        log::warn!("scancode queue uninitialized");
+    }
+}
+
+/// Number of lines Shift+PageUp/PageDown scroll the screen by.
+const SCROLL_PAGE_LINES: usize = vga_buffer::TEXT_HEIGHT - 1;
+
+static MODIFIERS: Mutex<Modifiers> = Mutex::new(Modifiers::new());
+
+/// Returns which modifier keys are currently held down or toggled on.
+pub fn modifiers() -> Modifiers {
+    *MODIFIERS.lock()
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Modifiers {
+    left_shift: bool,
+    right_shift: bool,
+    left_control: bool,
+    right_control: bool,
+    left_alt: bool,
+    right_alt: bool,
+    caps_lock: bool,
+}
+
+impl Modifiers {
+    const fn new() -> Modifiers {
+        Modifiers {
+            left_shift: false,
+            right_shift: false,
+            left_control: false,
+            right_control: false,
+            left_alt: false,
+            right_alt: false,
+            caps_lock: false,
+        }
+    }
+
+    fn update(&mut self, key_event: &KeyEvent) {
+        let pressed = key_event.state == KeyState::Down;
+        match key_event.code {
+            KeyCode::ShiftLeft => self.left_shift = pressed,
+            KeyCode::ShiftRight => self.right_shift = pressed,
+            KeyCode::ControlLeft => self.left_control = pressed,
+            KeyCode::ControlRight => self.right_control = pressed,
+            KeyCode::AltLeft => self.left_alt = pressed,
+            KeyCode::AltRight => self.right_alt = pressed,
+            KeyCode::CapsLock if pressed => self.caps_lock = !self.caps_lock,
+            _ => {}
+        }
+    }
+
+    pub fn shift(&self) -> bool {
+        self.left_shift || self.right_shift
+    }
+
+    pub fn control(&self) -> bool {
+        self.left_control || self.right_control
+    }
+
+    pub fn alt(&self) -> bool {
+        self.left_alt || self.right_alt
+    }
+
+    pub fn caps_lock(&self) -> bool {
+        self.caps_lock
+    }
+}
+
+/// Lists the active modifiers, e.g. `SHIFT ALT`.
+impl fmt::Display for Modifiers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names = [
+            (self.shift(), "SHIFT"),
+            (self.control(), "CTRL"),
+            (self.alt(), "ALT"),
+            (self.caps_lock(), "CAPS"),
+        ];
+        let mut separator = "";
+        for &(active, name) in names.iter() {
+            if active {
+                write!(f, "{}{}", separator, name)?;
+                separator = " ";
+            }
+        }
+        Ok(())
     }
 }
 
+/// Maps F1..F6 to the virtual console they switch to when pressed with Alt.
+fn console_for_key(key: KeyCode) -> Option<usize> {
+    match key {
+        KeyCode::F1 => Some(0),
+        KeyCode::F2 => Some(1),
+        KeyCode::F3 => Some(2),
+        KeyCode::F4 => Some(3),
+        KeyCode::F5 => Some(4),
+        KeyCode::F6 => Some(5),
+        _ => None,
+    }
+}
+
+/// Handles the console key bindings, returning whether `key` was consumed by one.
+fn handle_hotkey(key: &DecodedKey, modifiers: &Modifiers) -> bool {
+    let key = match key {
+        DecodedKey::RawKey(key) => *key,
+        DecodedKey::Unicode(_) => return false,
+    };
+
+    if modifiers.alt() {
+        if let Some(console) = console_for_key(key) {
+            vga_buffer::switch_console(console);
+            return true;
+        }
+    }
+    if modifiers.shift() {
+        match key {
+            KeyCode::PageUp => vga_buffer::scroll_view_up(SCROLL_PAGE_LINES),
+            KeyCode::PageDown => vga_buffer::scroll_view_down(SCROLL_PAGE_LINES),
+            _ => return false,
+        }
+        return true;
+    }
+    false
+}
+
 pub async fn print_keypresses() {
     let mut scancodes = ScancodeStream::new();
     let mut keyboard = Keyboard::new(layouts::Us104Key, ScancodeSet1, HandleControl::Ignore);
 
     while let Some(scancode) = scancodes.next().await {
         if let Ok(Some(key_event)) = keyboard.add_byte(scancode) {
+            let modifiers = {
+                let mut modifiers = MODIFIERS.lock();
+                modifiers.update(&key_event);
+                *modifiers
+            };
             if let Some(key) = keyboard.process_keyevent(key_event) {
+                if handle_hotkey(&key, &modifiers) {
+                    continue;
+                }
                 match key {
-                    DecodedKey::Unicode(character) => print!("{}", character),
-                    DecodedKey::RawKey(key) => print!("{:?}", key),
+                    DecodedKey::Unicode(character) => {
+                        console_print!(vga_buffer::active_console(), "{}", character)
+                    }
+                    DecodedKey::RawKey(key) => {
+                        console_print!(vga_buffer::active_console(), "{:?}", key)
+                    }
                 }
             }
         }
@@ -8,6 +8,11 @@ use core::{
 
 pub mod executor;
 pub mod keyboard;
+pub mod serial;
+pub mod status_bar;
+pub mod timer;
+
+pub use timer::{interval, sleep, sleep_until, timeout, Elapsed, Interval, Sleep, Timeout};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 struct TaskId(u64);
@@ -0,0 +1,117 @@
+use crate::{console_print, serial::ComPort, vga_buffer};
+use conquer_once::spin::OnceCell;
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use crossbeam_queue::ArrayQueue;
+use futures_util::{
+    stream::{Stream, StreamExt},
+    task::AtomicWaker,
+};
+
+static SERIAL_QUEUES: [OnceCell<ArrayQueue<u8>>; 4] = [
+    OnceCell::uninit(),
+    OnceCell::uninit(),
+    OnceCell::uninit(),
+    OnceCell::uninit(),
+];
+static WAKERS: [AtomicWaker; 4] = [
+    AtomicWaker::new(),
+    AtomicWaker::new(),
+    AtomicWaker::new(),
+    AtomicWaker::new(),
+];
+
+/// Bytes received on a serial port.
+pub struct SerialStream {
+    com: ComPort,
+}
+
+impl SerialStream {
+    pub fn new(com: ComPort) -> Self {
+        SERIAL_QUEUES[com as usize]
+            .try_init_once(|| ArrayQueue::new(256))
+            .expect("SerialStream::new should only be called once per port");
+        SerialStream { com }
+    }
+}
+
+impl Stream for SerialStream {
+    type Item = u8;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<u8>> {
+        let index = self.com as usize;
+        let queue = SERIAL_QUEUES[index]
+            .try_get()
+            .expect("serial queue not initialized");
+
+        if let Some(byte) = queue.pop() {
+            return Poll::Ready(Some(byte));
+        }
+
+        WAKERS[index].register(&cx.waker());
+        match queue.pop() {
+            Some(byte) => {
+                WAKERS[index].take();
+                Poll::Ready(Some(byte))
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// Called by the serial interrupt handlers.
+///
+/// Bytes arriving before a `SerialStream` for the port exists are dropped.
+pub(crate) fn add_byte(com: ComPort, byte: u8) {
+    if let Ok(queue) = SERIAL_QUEUES[com as usize].try_get() {
+        if queue.push(byte).is_err() {
+            log::warn!("{:?} input queue full; dropping input", com);
+        } else {
+            WAKERS[com as usize].wake();
+        }
+    }
+}
+
+/// Echoes characters typed on the serial console to the active virtual console.
+pub async fn print_serial_input() {
+    let mut bytes = SerialStream::new(ComPort::Com1);
+
+    while let Some(byte) = bytes.next().await {
+        match byte {
+            b'\r' => console_print!(vga_buffer::active_console(), "\n"),
+            0x7f => console_print!(vga_buffer::active_console(), "\x08 \x08"),
+            byte => console_print!(vga_buffer::active_console(), "{}", char::from(byte)),
+        }
+    }
+}
+
+#[test_case]
+fn test_serial_stream_yields_bytes_in_order() {
+    use futures_util::FutureExt;
+    use x86_64::instructions::interrupts;
+
+    // no other code reads COM4
+    let com = ComPort::Com4;
+    interrupts::without_interrupts(|| {
+        let mut bytes = SerialStream::new(com);
+        for &byte in b"dvOS" {
+            add_byte(com, byte);
+        }
+        for &byte in b"dvOS" {
+            assert_eq!(bytes.next().now_or_never(), Some(Some(byte)));
+        }
+        assert_eq!(bytes.next().now_or_never(), None);
+
+        // bytes that don't fit in the queue anymore are dropped
+        let capacity = SERIAL_QUEUES[com as usize].try_get().unwrap().capacity();
+        for index in 0..=capacity {
+            add_byte(com, index as u8);
+        }
+        for index in 0..capacity {
+            assert_eq!(bytes.next().now_or_never(), Some(Some(index as u8)));
+        }
+        assert_eq!(bytes.next().now_or_never(), None);
+    });
+}
@@ -0,0 +1,30 @@
+use super::{executor, keyboard, timer};
+use crate::{allocator, time, vga_buffer};
+use core::time::Duration;
+use futures_util::stream::StreamExt;
+
+/// How often the status bar is redrawn, quick enough to follow the modifier keys.
+const REDRAW_PERIOD: Duration = Duration::from_millis(100);
+
+pub async fn update_status_bar() {
+    let mut redraws = timer::interval(REDRAW_PERIOD);
+    draw();
+    while redraws.next().await.is_some() {
+        draw();
+    }
+}
+
+fn draw() {
+    let uptime = time::uptime().as_secs();
+    vga_buffer::set_status_line(format_args!(
+        " dvOS | tty{} | up {:02}:{:02}:{:02} | heap {}/{} KiB | tasks {} | {}",
+        vga_buffer::active_console() + 1,
+        uptime / 3600,
+        uptime / 60 % 60,
+        uptime % 60,
+        (allocator::heap_used() + 1023) / 1024,
+        allocator::HEAP_SIZE / 1024,
+        executor::task_count(),
+        keyboard::modifiers(),
+    ));
+}
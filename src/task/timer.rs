@@ -0,0 +1,218 @@
+//! Futures that wait for time to pass, woken from the timer interrupt.
+
+use crate::{sync::IrqSafeMutex, time::Instant};
+use alloc::collections::BinaryHeap;
+use core::{
+    cmp::{Ordering, Reverse},
+    future::Future,
+    mem,
+    pin::Pin,
+    sync::atomic::{self, AtomicU64},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+use futures_util::stream::Stream;
+use lazy_static::lazy_static;
+
+lazy_static! {
+    /// Pending timers, the earliest deadline first.
+    static ref TIMERS: IrqSafeMutex<BinaryHeap<Reverse<Timer>>> =
+        IrqSafeMutex::new(BinaryHeap::new());
+}
+
+struct Timer {
+    deadline: Instant,
+    id: u64,
+    waker: Waker,
+}
+
+impl PartialEq for Timer {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Timer {}
+
+impl PartialOrd for Timer {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Timer {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.deadline, self.id).cmp(&(other.deadline, other.id))
+    }
+}
+
+/// Called on every timer interrupt.
+pub(crate) fn tick() {
+    let now = Instant::now();
+    let mut timers = TIMERS.lock();
+    while timers.peek().map_or(false, |timer| timer.0.deadline <= now) {
+        // popping doesn't free memory, which the interrupted code may be doing; nor does
+        // dropping the waker, as the `Sleep` it came from still holds a clone
+        if let Some(Reverse(timer)) = timers.pop() {
+            timer.waker.wake();
+        }
+    }
+}
+
+/// Takes the timer off the queue, if the timer interrupt hasn't already.
+fn remove_timer(id: u64) {
+    let mut timers = TIMERS.lock();
+    let mut entries = mem::take(&mut *timers).into_vec();
+    entries.retain(|timer| timer.0.id != id);
+    *timers = BinaryHeap::from(entries);
+}
+
+/// Completes once `duration` has passed.
+pub fn sleep(duration: Duration) -> Sleep {
+    sleep_until(Instant::now() + duration)
+}
+
+/// Completes once the monotonic clock reaches `deadline`.
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+    Sleep {
+        deadline,
+        id: NEXT_ID.fetch_add(1, atomic::Ordering::Relaxed),
+        waker: None,
+    }
+}
+
+/// Future returned by `sleep` and `sleep_until`.
+///
+/// Its resolution is one timer interrupt, see `time::set_frequency`.
+pub struct Sleep {
+    deadline: Instant,
+    id: u64,
+    /// The waker on the timer queue, if it's there.
+    waker: Option<Waker>,
+}
+
+impl Sleep {
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Moves the deadline, for reusing the future.
+    pub fn reset(&mut self, deadline: Instant) {
+        self.cancel();
+        self.deadline = deadline;
+    }
+
+    fn cancel(&mut self) {
+        if self.waker.take().is_some() {
+            remove_timer(self.id);
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if Instant::now() >= self.deadline {
+            self.cancel();
+            return Poll::Ready(());
+        }
+        if let Some(waker) = &self.waker {
+            if waker.will_wake(cx.waker()) {
+                return Poll::Pending;
+            }
+            self.cancel();
+        }
+
+        let timer = Timer {
+            deadline: self.deadline,
+            id: self.id,
+            waker: cx.waker().clone(),
+        };
+        self.waker = Some(cx.waker().clone());
+        TIMERS.lock().push(Reverse(timer));
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        // the timer interrupt must not be left with the last clone of the waker
+        self.cancel();
+    }
+}
+
+/// Yields the instant of every `period` from now on.
+///
+/// Ticks that are missed because the consumer was busy are skipped.
+pub fn interval(period: Duration) -> Interval {
+    assert!(
+        period > Duration::from_nanos(0),
+        "interval period must not be zero"
+    );
+    Interval {
+        period,
+        sleep: sleep(period),
+    }
+}
+
+pub struct Interval {
+    period: Duration,
+    sleep: Sleep,
+}
+
+impl Stream for Interval {
+    type Item = Instant;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Instant>> {
+        match Pin::new(&mut self.sleep).poll(cx) {
+            Poll::Ready(()) => {
+                let tick = self.sleep.deadline();
+                let now = Instant::now();
+                let mut next = tick + self.period;
+                while next <= now {
+                    next += self.period;
+                }
+                self.sleep.reset(next);
+                Poll::Ready(Some(tick))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Error of a `timeout` whose future did not complete in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+/// Runs `future` for at most `duration`.
+pub fn timeout<F: Future>(duration: Duration, future: F) -> Timeout<F> {
+    Timeout {
+        future,
+        sleep: sleep(duration),
+    }
+}
+
+pub struct Timeout<F> {
+    future: F,
+    sleep: Sleep,
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        // `future` is never moved out of the pinned `Timeout`, and `Sleep` is `Unpin`
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        if let Poll::Ready(output) = future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        match Pin::new(&mut this.sleep).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(Elapsed)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! The programmable interval timer, which drives the timer interrupt, and a
+//! monotonic clock on top of it.
+
+use crate::{
+    interrupts::{self, InterruptIndex},
+    sync::IrqSafeMutex,
+};
+use core::{
+    ops::{Add, AddAssign, Sub},
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::Duration,
+};
+use x86_64::instructions::port::Port;
+
+/// Input clock of the programmable interval timer in Hz.
+const PIT_BASE_FREQUENCY: u32 = 1_193_182;
+
+/// Timer interrupts per second after `init`.
+pub const DEFAULT_FREQUENCY: u32 = 100;
+
+const PIT_CHANNEL_0: u16 = 0x40;
+const PIT_COMMAND: u16 = 0x43;
+/// Channel 0, low then high byte of the divisor, square wave mode.
+const PIT_SQUARE_WAVE: u8 = 0b0011_0110;
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+static TICKS: AtomicU64 = AtomicU64::new(0);
+static TICK_INDICATOR: AtomicBool = AtomicBool::new(false);
+
+/// The divisor in use, and where the clock stood when it was set.
+static CLOCK: IrqSafeMutex<Clock> = IrqSafeMutex::new(Clock {
+    // what the BIOS leaves the PIT at, about 18.2 Hz
+    divisor: 65536,
+    epoch_ticks: 0,
+    epoch_nanos: 0,
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeError {
+    /// The PIT can only divide its input clock by 1 to 65536.
+    UnsupportedFrequency(u32),
+}
+
+struct Clock {
+    divisor: u32,
+    epoch_ticks: u64,
+    epoch_nanos: u64,
+}
+
+impl Clock {
+    fn nanos_at(&self, ticks: u64) -> u64 {
+        let elapsed =
+            u128::from(ticks - self.epoch_ticks) * u128::from(self.divisor) * NANOS_PER_SEC
+                / u128::from(PIT_BASE_FREQUENCY);
+        self.epoch_nanos + elapsed as u64
+    }
+}
+
+/// Programs the PIT to `DEFAULT_FREQUENCY` and starts counting its interrupts.
+pub fn init() {
+    set_frequency(DEFAULT_FREQUENCY).expect("default timer frequency not supported");
+    interrupts::register_irq(InterruptIndex::Timer.irq(), |_| tick())
+        .expect("timer IRQ already taken");
+}
+
+/// Programs the PIT to interrupt about `hz` times per second and returns the
+/// frequency it got, which can only be the base frequency divided by an integer.
+pub fn set_frequency(hz: u32) -> Result<u32, TimeError> {
+    if hz == 0 || hz > PIT_BASE_FREQUENCY {
+        return Err(TimeError::UnsupportedFrequency(hz));
+    }
+    let divisor = (PIT_BASE_FREQUENCY + hz / 2) / hz;
+    if divisor > 65536 {
+        return Err(TimeError::UnsupportedFrequency(hz));
+    }
+
+    {
+        // held until the PIT is reprogrammed, so no tick is counted at the wrong rate
+        let mut clock = CLOCK.lock();
+        let ticks = ticks();
+        clock.epoch_nanos = clock.nanos_at(ticks);
+        clock.epoch_ticks = ticks;
+        clock.divisor = divisor;
+
+        // a divisor of 65536 is written as 0
+        let [low, high, ..] = divisor.to_le_bytes();
+        unsafe {
+            Port::<u8>::new(PIT_COMMAND).write(PIT_SQUARE_WAVE);
+            let mut channel_0 = Port::<u8>::new(PIT_CHANNEL_0);
+            channel_0.write(low);
+            channel_0.write(high);
+        }
+    }
+    Ok(frequency())
+}
+
+/// Timer interrupts per second, rounded to the nearest whole hertz.
+pub fn frequency() -> u32 {
+    let divisor = CLOCK.lock().divisor;
+    (PIT_BASE_FREQUENCY + divisor / 2) / divisor
+}
+
+fn tick() {
+    let ticks = TICKS.fetch_add(1, Ordering::Relaxed) + 1;
+    if TICK_INDICATOR.load(Ordering::Relaxed) && ticks % u64::from(frequency()) == 0 {
+        crate::print!(".");
+    }
+    crate::task::timer::tick();
+}
+
+/// Prints a '.' every second from the timer interrupt, to see that it keeps coming.
+pub fn set_tick_indicator(enabled: bool) {
+    TICK_INDICATOR.store(enabled, Ordering::Relaxed);
+}
+
+/// Number of timer interrupts since boot.
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Time since the timer interrupt was enabled, in steps of one tick.
+pub fn uptime() -> Duration {
+    Instant::now().0
+}
+
+/// A point in time on the monotonic clock, which starts with the timer interrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant(Duration);
+
+impl Instant {
+    pub fn now() -> Instant {
+        let nanos = CLOCK.lock().nanos_at(ticks());
+        Instant(Duration::from_nanos(nanos))
+    }
+
+    /// Time since boot at this instant.
+    pub fn since_boot(&self) -> Duration {
+        self.0
+    }
+
+    /// Time from `earlier` to this instant, or zero if `earlier` is later.
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        self.0.checked_sub(earlier.0).unwrap_or_default()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        Instant::now().duration_since(*self)
+    }
+
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        self.0.checked_add(duration).map(Instant)
+    }
+
+    pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+        self.0.checked_sub(duration).map(Instant)
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, duration: Duration) -> Instant {
+        self.checked_add(duration)
+            .expect("overflow when adding duration to instant")
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, duration: Duration) {
+        *self = *self + duration;
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    fn sub(self, duration: Duration) -> Instant {
+        self.checked_sub(duration)
+            .expect("overflow when subtracting duration from instant")
+    }
+}
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, earlier: Instant) -> Duration {
+        self.duration_since(earlier)
+    }
+}
+
+#[test_case]
+fn test_clock_survives_frequency_changes() {
+    let clock = Clock {
+        divisor: PIT_BASE_FREQUENCY / 1000,
+        epoch_ticks: 10,
+        epoch_nanos: 5_000_000_000,
+    };
+    // 1000 ticks at 1000 Hz are a second
+    assert_eq!(clock.nanos_at(1010) / 1_000_000, 5_999);
+    assert_eq!(clock.nanos_at(10), 5_000_000_000);
+
+    let start = Instant::now();
+    let before = ticks();
+    while ticks() < before + 2 {
+        x86_64::instructions::hlt();
+    }
+    assert!(start.elapsed() > Duration::from_nanos(0));
+    assert!(Instant::now() >= start);
+}
@@ -0,0 +1,309 @@
+//! A request/response protocol for moving data into and out of a running kernel over
+//! a serial port, e.g. a QEMU chardev pipe.
+//!
+//! The host sends one request frame at a time and waits for the response with the
+//! same sequence number. Corrupted frames are answered with `NAK` and should be sent
+//! again; a repeated sequence number gets the previous response resent without
+//! running the request twice, so the host must advance it for every new request.
+
+use crate::{
+    allocator,
+    serial::{self, ComPort, LineSettings, SerialError},
+    task::{executor, serial::SerialStream},
+    time,
+};
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::fmt::Write;
+use frame::{Decoder, Frame, MAX_PAYLOAD};
+use futures_util::stream::StreamExt;
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+pub mod frame;
+
+/// The port the protocol runs on, leaving COM1 to the console and COM2 to GDB.
+pub const PORT: ComPort = ComPort::Com3;
+
+/// Stores `data` at `offset` in a blob: `name_len u8 | name | offset u32 | data`.
+///
+/// Offset 0 starts the blob over. Responds with the blob's new size as a `u32`.
+pub const PUT_BLOB: u8 = 0x01;
+/// Reads from a blob: `name_len u8 | name | offset u32 | length u16`.
+///
+/// Responds with at most `length` bytes; fewer at the end of the blob.
+pub const GET_BLOB: u8 = 0x02;
+/// Runs a command line and responds with its output.
+pub const RUN_COMMAND: u8 = 0x03;
+/// Responds with `key=value` lines about the kernel.
+pub const STATUS: u8 = 0x04;
+
+/// Set in the kind of a successful response.
+pub const RESPONSE: u8 = 0x80;
+/// Kind of a failed request's response, which carries the reason as text.
+pub const ERROR: u8 = 0xfe;
+/// Asks the other side to send its last frame again.
+pub const NAK: u8 = 0xff;
+
+/// Total bytes the blobs may take up, so uploads can't exhaust the heap.
+const MAX_BLOB_BYTES: usize = 32 * 1024;
+
+const MAX_COMMANDS: usize = 16;
+
+/// Runs with the arguments of a command line and writes its output to the response.
+pub type Command = fn(args: &str, output: &mut Frame) -> Result<(), &'static str>;
+
+#[derive(Debug)]
+pub enum RegisterError {
+    TooManyCommands,
+}
+
+lazy_static! {
+    static ref BLOBS: Mutex<BTreeMap<String, Vec<u8>>> = Mutex::new(BTreeMap::new());
+}
+
+static COMMANDS: Mutex<[Option<(&'static str, Command)>; MAX_COMMANDS]> =
+    Mutex::new([None; MAX_COMMANDS]);
+
+const BUILTIN_COMMANDS: &[(&str, Command)] = &[
+    ("echo", echo),
+    ("ls", ls),
+    ("rm", rm),
+    ("crc", crc),
+    ("interrupts", interrupts),
+];
+
+/// Sets up `PORT` for the protocol; `serve` must run for requests to be answered.
+pub fn init() -> Result<(), SerialError> {
+    let settings = LineSettings {
+        baud_rate: 115_200,
+        ..LineSettings::new()
+    };
+    serial::configure(PORT, settings)?;
+    serial::enable_receive_interrupt(PORT);
+    Ok(())
+}
+
+/// Makes `command` available to `RUN_COMMAND`, replacing one of the same name.
+pub fn register_command(name: &'static str, command: Command) -> Result<(), RegisterError> {
+    let mut commands = COMMANDS.lock();
+    let slot = match commands
+        .iter()
+        .position(|entry| matches!(entry, Some((existing, _)) if *existing == name))
+    {
+        Some(index) => index,
+        None => commands
+            .iter()
+            .position(Option::is_none)
+            .ok_or(RegisterError::TooManyCommands)?,
+    };
+    commands[slot] = Some((name, command));
+    Ok(())
+}
+
+/// Calls `f` with the contents of an uploaded blob.
+pub fn with_blob<F, R>(name: &str, f: F) -> Option<R>
+where
+    F: FnOnce(&[u8]) -> R,
+{
+    BLOBS.lock().get(name).map(|blob| f(blob))
+}
+
+/// Answers requests arriving on `PORT`.
+pub async fn serve() {
+    let mut bytes = SerialStream::new(PORT);
+    let mut decoder = Decoder::new();
+    let mut last_response: Option<Frame> = None;
+
+    while let Some(byte) = bytes.next().await {
+        let request = match decoder.push(byte) {
+            None => continue,
+            Some(Ok(request)) => request,
+            Some(Err(error)) => {
+                log::debug!("dropping corrupted frame: {:?}", error);
+                send(&Frame::new(0, NAK));
+                continue;
+            }
+        };
+
+        match &last_response {
+            Some(response) if request.kind == NAK || request.seq == response.seq => {
+                send(response);
+                continue;
+            }
+            _ => {}
+        }
+        if request.kind == NAK {
+            continue;
+        }
+
+        let mut response = Frame::new(request.seq, request.kind | RESPONSE);
+        if let Err(message) = handle(request, &mut response) {
+            response = Frame::new(request.seq, ERROR);
+            response.push(message.as_bytes());
+        }
+        send(&response);
+        last_response = Some(response);
+    }
+}
+
+fn send(frame: &Frame) {
+    // byte by byte, so a long frame doesn't hold off the timer
+    frame.encode(|byte| serial::port(PORT).lock().send(byte));
+}
+
+fn handle(request: &Frame, response: &mut Frame) -> Result<(), &'static str> {
+    let mut payload = Reader(request.payload());
+    match request.kind {
+        PUT_BLOB => {
+            let name = payload.name()?;
+            let offset = payload.u32()? as usize;
+            let data = payload.0;
+
+            let mut blobs = BLOBS.lock();
+            let stored: usize = blobs
+                .iter()
+                .filter(|(blob, _)| blob.as_str() != name)
+                .map(|(_, blob)| blob.len())
+                .sum();
+            let previous = if offset == 0 {
+                0
+            } else {
+                blobs.get(name).map_or(0, Vec::len)
+            };
+            if offset > previous {
+                return Err("offset past the end of the blob");
+            }
+            let size = previous.max(offset + data.len());
+            if stored + size > MAX_BLOB_BYTES {
+                return Err("out of blob space");
+            }
+
+            let blob = blobs.entry(String::from(name)).or_insert_with(Vec::new);
+            blob.resize(previous, 0);
+            blob.resize(size, 0);
+            blob[offset..offset + data.len()].copy_from_slice(data);
+            response.push(&(size as u32).to_le_bytes());
+        }
+        GET_BLOB => {
+            let name = payload.name()?;
+            let offset = payload.u32()? as usize;
+            let length = usize::from(payload.u16()?).min(MAX_PAYLOAD);
+
+            let blobs = BLOBS.lock();
+            let blob = blobs.get(name).ok_or("no such blob")?;
+            let start = offset.min(blob.len());
+            let end = (start + length).min(blob.len());
+            response.push(&blob[start..end]);
+        }
+        RUN_COMMAND => {
+            let line = core::str::from_utf8(payload.0).map_err(|_| "command is not UTF-8")?;
+            let line = line.trim();
+            let (name, args) = match line.find(' ') {
+                Some(index) => (&line[..index], line[index + 1..].trim_start()),
+                None => (line, ""),
+            };
+            let command = find_command(name).ok_or("unknown command")?;
+            command(args, response)?;
+        }
+        STATUS => {
+            let millis = time::uptime().as_millis();
+            let (blobs, blob_bytes) = {
+                let blobs = BLOBS.lock();
+                (blobs.len(), blobs.values().map(Vec::len).sum::<usize>())
+            };
+            let _ = write!(
+                response,
+                "uptime_ms={}\nheap_used={}\nheap_size={}\ntasks={}\nblobs={}\nblob_bytes={}\n",
+                millis,
+                allocator::heap_used(),
+                allocator::HEAP_SIZE,
+                executor::task_count(),
+                blobs,
+                blob_bytes
+            );
+        }
+        _ => return Err("unknown request"),
+    }
+    Ok(())
+}
+
+fn find_command(name: &str) -> Option<Command> {
+    let registered = COMMANDS
+        .lock()
+        .iter()
+        .flatten()
+        .find(|(registered, _)| *registered == name)
+        .map(|&(_, command)| command);
+    registered.or_else(|| {
+        BUILTIN_COMMANDS
+            .iter()
+            .find(|(builtin, _)| *builtin == name)
+            .map(|&(_, command)| command)
+    })
+}
+
+/// Takes the fields of a request payload off its front.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], &'static str> {
+        if self.0.len() < count {
+            return Err("request too short");
+        }
+        let (bytes, rest) = self.0.split_at(count);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, &'static str> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, &'static str> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// A blob name, prefixed with its length as a byte.
+    fn name(&mut self) -> Result<&'a str, &'static str> {
+        let len = self.bytes(1)?[0];
+        let name = self.bytes(usize::from(len))?;
+        match core::str::from_utf8(name) {
+            Ok(name) if !name.is_empty() => Ok(name),
+            _ => Err("invalid blob name"),
+        }
+    }
+}
+
+fn echo(args: &str, output: &mut Frame) -> Result<(), &'static str> {
+    let _ = writeln!(output, "{}", args);
+    Ok(())
+}
+
+fn ls(_args: &str, output: &mut Frame) -> Result<(), &'static str> {
+    for (name, blob) in BLOBS.lock().iter() {
+        let _ = writeln!(output, "{:>8} {}", blob.len(), name);
+    }
+    Ok(())
+}
+
+fn interrupts(_args: &str, output: &mut Frame) -> Result<(), &'static str> {
+    let _ = write!(output, "{}", crate::interrupts::interrupt_table());
+    Ok(())
+}
+
+fn rm(args: &str, _output: &mut Frame) -> Result<(), &'static str> {
+    BLOBS.lock().remove(args).map(|_| ()).ok_or("no such blob")
+}
+
+fn crc(args: &str, output: &mut Frame) -> Result<(), &'static str> {
+    let crc = with_blob(args, |blob| {
+        let mut crc = frame::Crc32::new();
+        crc.update(blob);
+        crc.finish()
+    })
+    .ok_or("no such blob")?;
+    let _ = writeln!(output, "{:08x}", crc);
+    Ok(())
+}
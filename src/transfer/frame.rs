@@ -0,0 +1,225 @@
+//! Framing of transfer messages:
+//! `SYNC | seq | kind | len (u16 LE) | payload | CRC-32 (LE)`.
+//!
+//! The checksum is the IEEE CRC-32 of everything between `SYNC` and itself.
+
+use core::fmt;
+
+pub const SYNC: u8 = 0x7e;
+
+/// Largest payload of a frame in either direction.
+pub const MAX_PAYLOAD: usize = 1024;
+
+const HEADER_LEN: usize = 4;
+
+pub struct Frame {
+    pub seq: u8,
+    pub kind: u8,
+    len: usize,
+    payload: [u8; MAX_PAYLOAD],
+}
+
+impl Frame {
+    pub const fn new(seq: u8, kind: u8) -> Frame {
+        Frame {
+            seq,
+            kind,
+            len: 0,
+            payload: [0; MAX_PAYLOAD],
+        }
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.payload[..self.len]
+    }
+
+    /// Appends as much of `bytes` as fits and returns how much that was.
+    pub fn push(&mut self, bytes: &[u8]) -> usize {
+        let count = bytes.len().min(MAX_PAYLOAD - self.len);
+        self.payload[self.len..self.len + count].copy_from_slice(&bytes[..count]);
+        self.len += count;
+        count
+    }
+
+    fn header(&self) -> [u8; HEADER_LEN] {
+        let len = (self.len as u16).to_le_bytes();
+        [self.seq, self.kind, len[0], len[1]]
+    }
+
+    /// Passes the encoded frame to `send`, byte by byte.
+    pub fn encode(&self, mut send: impl FnMut(u8)) {
+        let header = self.header();
+        let mut crc = Crc32::new();
+        crc.update(&header);
+        crc.update(self.payload());
+
+        send(SYNC);
+        header
+            .iter()
+            .chain(self.payload())
+            .for_each(|&byte| send(byte));
+        crc.finish()
+            .to_le_bytes()
+            .iter()
+            .for_each(|&byte| send(byte));
+    }
+}
+
+/// Text written to a frame is cut off once the payload is full.
+impl fmt::Write for Frame {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push(s.as_bytes());
+        Ok(())
+    }
+}
+
+pub struct Crc32(u32);
+
+impl Crc32 {
+    pub const fn new() -> Crc32 {
+        Crc32(!0)
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u32::from(byte);
+            for _ in 0..8 {
+                let mask = (self.0 & 1).wrapping_neg();
+                self.0 = (self.0 >> 1) ^ (0xedb8_8320 & mask);
+            }
+        }
+    }
+
+    pub fn finish(&self) -> u32 {
+        !self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    PayloadTooLong(u16),
+    BadChecksum,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Sync,
+    Header(usize),
+    Payload(usize),
+    Checksum(usize),
+}
+
+/// Reassembles frames from a stream of bytes.
+pub struct Decoder {
+    state: State,
+    header: [u8; HEADER_LEN],
+    checksum: [u8; 4],
+    frame: Frame,
+}
+
+impl Decoder {
+    pub const fn new() -> Decoder {
+        Decoder {
+            state: State::Sync,
+            header: [0; HEADER_LEN],
+            checksum: [0; 4],
+            frame: Frame::new(0, 0),
+        }
+    }
+
+    /// Feeds the next byte, returning the frame or error it completes.
+    ///
+    /// Anything before a `SYNC` byte is skipped.
+    pub fn push(&mut self, byte: u8) -> Option<Result<&Frame, DecodeError>> {
+        match self.state {
+            State::Sync => {
+                if byte == SYNC {
+                    self.state = State::Header(0);
+                }
+            }
+            State::Header(index) => {
+                self.header[index] = byte;
+                if index + 1 < HEADER_LEN {
+                    self.state = State::Header(index + 1);
+                    return None;
+                }
+                let len = u16::from_le_bytes([self.header[2], self.header[3]]);
+                if usize::from(len) > MAX_PAYLOAD {
+                    self.state = State::Sync;
+                    return Some(Err(DecodeError::PayloadTooLong(len)));
+                }
+                self.frame.seq = self.header[0];
+                self.frame.kind = self.header[1];
+                self.frame.len = usize::from(len);
+                self.state = if len == 0 {
+                    State::Checksum(0)
+                } else {
+                    State::Payload(0)
+                };
+            }
+            State::Payload(index) => {
+                self.frame.payload[index] = byte;
+                self.state = if index + 1 < self.frame.len {
+                    State::Payload(index + 1)
+                } else {
+                    State::Checksum(0)
+                };
+            }
+            State::Checksum(index) => {
+                self.checksum[index] = byte;
+                if index + 1 < self.checksum.len() {
+                    self.state = State::Checksum(index + 1);
+                    return None;
+                }
+                self.state = State::Sync;
+                let mut crc = Crc32::new();
+                crc.update(&self.header);
+                crc.update(self.frame.payload());
+                if crc.finish() != u32::from_le_bytes(self.checksum) {
+                    return Some(Err(DecodeError::BadChecksum));
+                }
+                return Some(Ok(&self.frame));
+            }
+        }
+        None
+    }
+}
+
+#[test_case]
+fn test_frame_round_trip_and_corruption() {
+    let mut frame = Frame::new(7, 0x04);
+    frame.push(b"hello");
+
+    let mut encoded = [0u8; 16];
+    let mut len = 0;
+    frame.encode(|byte| {
+        encoded[len] = byte;
+        len += 1;
+    });
+    assert_eq!(len, 1 + HEADER_LEN + 5 + 4);
+
+    let mut decoder = Decoder::new();
+    // noise before the frame is skipped
+    assert!(decoder.push(0x00).is_none());
+    for &byte in &encoded[..len - 1] {
+        assert!(decoder.push(byte).is_none());
+    }
+    match decoder.push(encoded[len - 1]) {
+        Some(Ok(decoded)) => {
+            assert_eq!((decoded.seq, decoded.kind), (7, 0x04));
+            assert_eq!(decoded.payload(), b"hello");
+        }
+        _ => panic!("frame not decoded"),
+    }
+
+    encoded[6] ^= 0x20;
+    let mut result = None;
+    for &byte in &encoded[..len] {
+        result = decoder.push(byte).map(|result| result.map(|_| ()));
+    }
+    assert_eq!(result, Some(Err(DecodeError::BadChecksum)));
+
+    let mut crc = Crc32::new();
+    crc.update(b"123456789");
+    assert_eq!(crc.finish(), 0xcbf4_3926);
+}
@@ -1,8 +1,18 @@
+use crate::{framebuffer, sync::IrqSafeMutex};
+use ansi::{Action, Csi, Parser};
 use core::fmt;
+pub use cursor::CursorShape;
 use lazy_static::lazy_static;
-use spin::Mutex;
+use scrollback::Scrollback;
+pub use tui::{BoxStyle, Rect};
 use volatile::Volatile;
 
+pub(crate) mod ansi;
+mod cp437;
+mod cursor;
+mod scrollback;
+mod tui;
+
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
@@ -25,6 +35,47 @@ pub enum Color {
     White = 15,
 }
 
+impl From<u8> for Color {
+    fn from(value: u8) -> Color {
+        match value & 0x0f {
+            0 => Color::Black,
+            1 => Color::Blue,
+            2 => Color::Green,
+            3 => Color::Cyan,
+            4 => Color::Red,
+            5 => Color::Magenta,
+            6 => Color::Brown,
+            7 => Color::LightGray,
+            8 => Color::DarkGray,
+            9 => Color::LightBlue,
+            10 => Color::LightGreen,
+            11 => Color::LightCyan,
+            12 => Color::LightRed,
+            13 => Color::Pink,
+            14 => Color::Yellow,
+            _ => Color::White,
+        }
+    }
+}
+
+/// VGA colors in the order of the ANSI color indices 0-7 (black, red, green,
+/// yellow, blue, magenta, cyan, white); adding 8 gives the bright variant.
+const ANSI_COLORS: [Color; 8] = [
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Brown,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::LightGray,
+];
+
+fn ansi_color(index: u16, bright: bool) -> Color {
+    let color = ANSI_COLORS[usize::from(index % 8)] as u8;
+    Color::from(if bright { color | 0x08 } else { color })
+}
+
 pub const DEFAULT_FOREGROUND_COLOR: Color = Color::White;
 pub const DEFAULT_BACKGROUND_COLOR: Color = Color::Black;
 
@@ -36,6 +87,14 @@ impl ColorCode {
     pub fn new(foreground: Color, background: Color) -> ColorCode {
         ColorCode((background as u8) << 4 | (foreground as u8))
     }
+
+    pub fn foreground(self) -> Color {
+        Color::from(self.0)
+    }
+
+    pub fn background(self) -> Color {
+        Color::from(self.0 >> 4)
+    }
 }
 
 #[macro_export]
@@ -54,27 +113,137 @@ macro_rules! color_code {
     };
 }
 
+/// Returns the SGR foreground and background parameters that select the colors of
+/// `color_code` on an ANSI terminal.
+pub(crate) fn sgr_params(color_code: ColorCode) -> (u16, u16) {
+    let ansi_index = |color: Color| {
+        let base = color as u8 & 0x07;
+        let index = ANSI_COLORS
+            .iter()
+            .position(|&c| c as u8 == base)
+            .unwrap_or(0);
+        (index as u16, color as u8 & 0x08 != 0)
+    };
+    let (foreground, bright_foreground) = ansi_index(color_code.foreground());
+    let (background, bright_background) = ansi_index(color_code.background());
+    (
+        foreground + if bright_foreground { 90 } else { 30 },
+        background + if bright_background { 100 } else { 40 },
+    )
+}
+
+/// Applies the SGR parameters of an `ESC [ ... m` sequence to `color_code`.
+pub(crate) fn select_graphic_rendition(
+    color_code: ColorCode,
+    bold: &mut bool,
+    params: &[u16],
+) -> ColorCode {
+    if params.is_empty() {
+        *bold = false;
+        return color_code!();
+    }
+
+    let mut foreground = color_code.foreground();
+    let mut background = color_code.background();
+    for &param in params {
+        match param {
+            0 => {
+                *bold = false;
+                foreground = DEFAULT_FOREGROUND_COLOR;
+                background = DEFAULT_BACKGROUND_COLOR;
+            }
+            1 => {
+                *bold = true;
+                foreground = Color::from(foreground as u8 | 0x08);
+            }
+            22 => {
+                *bold = false;
+                foreground = Color::from(foreground as u8 & !0x08);
+            }
+            30..=37 => foreground = ansi_color(param - 30, *bold),
+            39 => foreground = DEFAULT_FOREGROUND_COLOR,
+            40..=47 => background = ansi_color(param - 40, false),
+            49 => background = DEFAULT_BACKGROUND_COLOR,
+            90..=97 => foreground = ansi_color(param - 90, true),
+            100..=107 => background = ansi_color(param - 100, true),
+            _ => {}
+        }
+    }
+    ColorCode::new(foreground, background)
+}
+
+/// One character cell of the text buffer; `ascii_character` is a code page 437 glyph.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(C)]
-struct ScreenChar {
-    ascii_character: u8,
-    color_code: ColorCode,
+pub struct ScreenChar {
+    pub ascii_character: u8,
+    pub color_code: ColorCode,
 }
 
-const BUFFER_HEIGHT: usize = 25;
-const BUFFER_WIDTH: usize = 80;
+pub const BUFFER_HEIGHT: usize = 25;
+pub const BUFFER_WIDTH: usize = 80;
+
+/// Screen row reserved for the status bar; console output never writes or scrolls it.
+pub const STATUS_ROW: usize = 0;
+
+/// First screen row that console output is written to.
+const TEXT_TOP: usize = STATUS_ROW + 1;
+
+/// Number of rows available to console output.
+pub const TEXT_HEIGHT: usize = BUFFER_HEIGHT - TEXT_TOP;
 
 struct Buffer {
     chars: [[Volatile<ScreenChar>; BUFFER_WIDTH]; BUFFER_HEIGHT],
 }
 
+/// Off-screen copy of a console's contents.
+type ScreenBuffer = [[ScreenChar; BUFFER_WIDTH]; BUFFER_HEIGHT];
+
+const BLANK_SCREEN_CHAR: ScreenChar = ScreenChar {
+    ascii_character: b' ',
+    color_code: ColorCode((DEFAULT_BACKGROUND_COLOR as u8) << 4 | DEFAULT_FOREGROUND_COLOR as u8),
+};
+
+const STATUS_BLANK_SCREEN_CHAR: ScreenChar = ScreenChar {
+    ascii_character: b' ',
+    color_code: ColorCode((Color::LightGray as u8) << 4 | Color::Black as u8),
+};
+
+const TAB_WIDTH: usize = 8;
+
 pub struct Writer {
+    row_position: usize,
     column_position: usize,
     color_code: ColorCode,
-    buffer: &'static mut Buffer,
+    bold: bool,
+    saved_position: (usize, usize),
+    cursor_visible: bool,
+    cursor_shape: CursorShape,
+    parser: Parser,
+    scrollback: Option<Scrollback>,
+    view_offset: usize,
+    shadow: &'static mut ScreenBuffer,
+    screen: Option<&'static mut Buffer>,
 }
 
 impl Writer {
+    fn new(shadow: &'static mut ScreenBuffer) -> Writer {
+        Writer {
+            row_position: BUFFER_HEIGHT - 1,
+            column_position: 0,
+            color_code: color_code!(),
+            bold: false,
+            saved_position: (BUFFER_HEIGHT - 1, 0),
+            cursor_visible: true,
+            cursor_shape: CursorShape::Underline,
+            parser: Parser::new(),
+            scrollback: None,
+            view_offset: 0,
+            shadow,
+            screen: None,
+        }
+    }
+
     pub fn write_byte(&mut self, byte: u8) {
         match byte {
             b'\n' => self.new_line(),
@@ -83,44 +252,300 @@ impl Writer {
                     self.new_line();
                 }
 
-                let row = BUFFER_HEIGHT - 1;
+                let row = self.row_position;
                 let col = self.column_position;
 
                 let color_code = self.color_code;
-                self.buffer.chars[row][col].write(ScreenChar {
-                    ascii_character: byte,
-                    color_code,
-                });
+                self.write_cell(
+                    row,
+                    col,
+                    ScreenChar {
+                        ascii_character: byte,
+                        color_code,
+                    },
+                );
                 self.column_position += 1;
             }
         }
     }
 
+    /// Writes the string, interpreting ANSI/VT100 escape sequences and translating
+    /// every other character to its code page 437 glyph.
     pub fn write_string(&mut self, s: &str) {
-        s.bytes().for_each(|byte| match byte {
-            0x20..=0x7e | b'\n' => self.write_byte(byte),
-            _ => self.write_byte(0xfe),
-        });
+        self.reset_view();
+        for character in s.chars() {
+            match self.parser.advance(character) {
+                Some(Action::Print(character)) => self.write_byte(cp437::encode(character)),
+                Some(Action::Execute(byte)) => self.execute(byte),
+                Some(Action::Csi(csi)) => self.apply_csi(&csi),
+                Some(Action::SaveCursor) => self.save_cursor(),
+                Some(Action::RestoreCursor) => self.restore_cursor(),
+                Some(Action::Reset) => {
+                    self.reset_attributes();
+                    self.erase_display(2);
+                    self.row_position = TEXT_TOP;
+                    self.column_position = 0;
+                }
+                None => {}
+            }
+        }
+        self.update_cursor();
+    }
+
+    pub fn show_cursor(&mut self) {
+        self.cursor_visible = true;
+        self.apply_cursor();
+    }
+
+    pub fn hide_cursor(&mut self) {
+        self.cursor_visible = false;
+        self.apply_cursor();
+    }
+
+    pub fn set_cursor_shape(&mut self, shape: CursorShape) {
+        self.cursor_shape = shape;
+        self.apply_cursor();
+    }
+
+    /// Whether this console is the one currently shown on the screen.
+    pub fn is_displayed(&self) -> bool {
+        self.screen.is_some()
+    }
+
+    /// Starts keeping up to `lines` rows that scroll off the top of the screen.
+    pub fn enable_scrollback(&mut self, lines: usize) {
+        self.reset_view();
+        self.scrollback = Some(Scrollback::new(lines));
+    }
+
+    /// Shows rows from further back in the scrollback history.
+    pub fn scroll_view_up(&mut self, lines: usize) {
+        let history = match &self.scrollback {
+            Some(scrollback) => scrollback.len(),
+            None => return,
+        };
+        self.set_view_offset((self.view_offset + lines).min(history));
+    }
+
+    /// Shows more recent rows, returning to the live screen at the bottom.
+    pub fn scroll_view_down(&mut self, lines: usize) {
+        self.set_view_offset(self.view_offset.saturating_sub(lines));
+    }
+
+    /// Returns from the scrollback view to the live screen.
+    pub fn reset_view(&mut self) {
+        self.set_view_offset(0);
+    }
+
+    fn set_view_offset(&mut self, offset: usize) {
+        if offset == self.view_offset {
+            return;
+        }
+        self.view_offset = offset;
+        if offset == 0 {
+            self.redraw();
+            return;
+        }
+
+        let screen = match &mut self.screen {
+            Some(screen) => screen,
+            None => return,
+        };
+        cursor::disable();
+        let history = self.scrollback.as_ref().map_or(0, |s| s.len());
+        for row in TEXT_TOP..BUFFER_HEIGHT {
+            let index = history - offset + row - TEXT_TOP;
+            let line = match &self.scrollback {
+                Some(scrollback) if index < history => scrollback.line(index),
+                _ => &self.shadow[TEXT_TOP + index - history],
+            };
+            for (col, &screen_char) in line.iter().enumerate() {
+                screen.chars[row][col].write(screen_char);
+            }
+        }
+    }
+
+    /// Starts showing this console on `screen`.
+    fn attach(&mut self, screen: &'static mut Buffer) {
+        self.screen = Some(screen);
+        self.view_offset = 0;
+        self.redraw();
+    }
+
+    /// Stops showing this console, handing back the screen it was drawn on.
+    fn detach(&mut self) -> Option<&'static mut Buffer> {
+        self.view_offset = 0;
+        self.screen.take()
+    }
+
+    /// Copies the off-screen buffer to the screen, if the console is shown.
+    fn redraw(&mut self) {
+        if let Some(screen) = &mut self.screen {
+            for (row, line) in self.shadow.iter().enumerate().skip(TEXT_TOP) {
+                for (col, &screen_char) in line.iter().enumerate() {
+                    screen.chars[row][col].write(screen_char);
+                }
+            }
+        }
+        self.apply_cursor();
+    }
+
+    /// Programs the hardware cursor from this console's cursor state.
+    fn apply_cursor(&self) {
+        if !self.is_displayed() || self.view_offset != 0 {
+            return;
+        }
+        if self.cursor_visible {
+            cursor::enable(self.cursor_shape);
+            self.update_cursor();
+        } else {
+            cursor::disable();
+        }
+    }
+
+    /// Moves the hardware cursor to the current write position.
+    fn update_cursor(&self) {
+        if self.cursor_visible && self.is_displayed() {
+            let col = self.column_position.min(BUFFER_WIDTH - 1);
+            cursor::set_position(self.row_position, col, BUFFER_WIDTH);
+        }
+    }
+
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            b'\n' => self.new_line(),
+            b'\r' => self.column_position = 0,
+            b'\t' => {
+                let next_stop = (self.column_position / TAB_WIDTH + 1) * TAB_WIDTH;
+                self.column_position = next_stop.min(BUFFER_WIDTH - 1);
+            }
+            0x08 => self.column_position = self.column_position.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    fn apply_csi(&mut self, csi: &Csi) {
+        if csi.is_private() {
+            // DECTCEM: `ESC [ ? 25 h` shows and `ESC [ ? 25 l` hides the cursor
+            match (csi.params(), csi.final_byte()) {
+                ([25], b'h') => self.show_cursor(),
+                ([25], b'l') => self.hide_cursor(),
+                _ => {}
+            }
+            return;
+        }
+
+        let count = usize::from(csi.param(0, 1));
+        match csi.final_byte() {
+            b'A' => self.row_position = self.row_position.saturating_sub(count).max(TEXT_TOP),
+            b'B' => self.row_position = (self.row_position + count).min(BUFFER_HEIGHT - 1),
+            b'C' => self.column_position = (self.column_position + count).min(BUFFER_WIDTH - 1),
+            b'D' => self.column_position = self.column_position.saturating_sub(count),
+            b'H' | b'f' => {
+                let row = usize::from(csi.param(0, 1)).max(1).min(TEXT_HEIGHT);
+                let col = usize::from(csi.param(1, 1)).max(1).min(BUFFER_WIDTH);
+                self.row_position = TEXT_TOP + row - 1;
+                self.column_position = col - 1;
+            }
+            b'J' => self.erase_display(csi.param(0, 0)),
+            b'K' => self.erase_line(csi.param(0, 0)),
+            b'm' => self.select_graphic_rendition(csi.params()),
+            b's' => self.save_cursor(),
+            b'u' => self.restore_cursor(),
+            _ => {}
+        }
+    }
+
+    fn select_graphic_rendition(&mut self, params: &[u16]) {
+        self.color_code = select_graphic_rendition(self.color_code, &mut self.bold, params);
+    }
+
+    fn reset_attributes(&mut self) {
+        self.bold = false;
+        self.color_code = color_code!();
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved_position = (self.row_position, self.column_position);
+    }
+
+    fn restore_cursor(&mut self) {
+        let (row, col) = self.saved_position;
+        self.row_position = row;
+        self.column_position = col;
+    }
+
+    /// Erases part of the screen: 0 = cursor to end, 1 = start to cursor, 2 = everything.
+    fn erase_display(&mut self, mode: u16) {
+        match mode {
+            0 => {
+                self.erase_line(0);
+                for row in self.row_position + 1..BUFFER_HEIGHT {
+                    self.clear_row(row);
+                }
+            }
+            1 => {
+                for row in TEXT_TOP..self.row_position {
+                    self.clear_row(row);
+                }
+                self.erase_line(1);
+            }
+            2 | 3 => {
+                for row in TEXT_TOP..BUFFER_HEIGHT {
+                    self.clear_row(row);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Erases part of the current row: 0 = cursor to end, 1 = start to cursor, 2 = whole row.
+    fn erase_line(&mut self, mode: u16) {
+        let col = self.column_position.min(BUFFER_WIDTH - 1);
+        match mode {
+            0 => self.clear_cells(self.row_position, col, BUFFER_WIDTH),
+            1 => self.clear_cells(self.row_position, 0, col + 1),
+            2 => self.clear_row(self.row_position),
+            _ => {}
+        }
     }
 
     fn new_line(&mut self) {
-        for row in 1..BUFFER_HEIGHT {
-            for col in 0..BUFFER_WIDTH {
-                let character = self.buffer.chars[row][col].read();
-                self.buffer.chars[row - 1][col].write(character);
+        if self.row_position < BUFFER_HEIGHT - 1 {
+            self.row_position += 1;
+        } else {
+            if let Some(scrollback) = &mut self.scrollback {
+                scrollback.push(self.shadow[TEXT_TOP]);
             }
+            self.shadow.copy_within(TEXT_TOP + 1.., TEXT_TOP);
+            self.shadow[BUFFER_HEIGHT - 1] = [self.blank(); BUFFER_WIDTH];
+            self.redraw();
         }
-        self.clear_row(BUFFER_HEIGHT - 1);
         self.column_position = 0;
     }
 
-    fn clear_row(&mut self, row: usize) {
-        let blank = ScreenChar {
+    fn blank(&self) -> ScreenChar {
+        ScreenChar {
             ascii_character: b' ',
             color_code: self.color_code,
-        };
-        for col in 0..BUFFER_WIDTH {
-            self.buffer.chars[row][col].write(blank);
+        }
+    }
+
+    fn clear_row(&mut self, row: usize) {
+        self.clear_cells(row, 0, BUFFER_WIDTH);
+    }
+
+    fn clear_cells(&mut self, row: usize, start_col: usize, end_col: usize) {
+        let blank = self.blank();
+        for col in start_col..end_col {
+            self.write_cell(row, col, blank);
+        }
+    }
+
+    fn write_cell(&mut self, row: usize, col: usize, screen_char: ScreenChar) {
+        self.shadow[row][col] = screen_char;
+        if let Some(screen) = &mut self.screen {
+            screen.chars[row][col].write(screen_char);
         }
     }
 }
@@ -132,56 +557,233 @@ impl fmt::Write for Writer {
     }
 }
 
+pub const CONSOLE_COUNT: usize = 6;
+
+/// Console that `print!` and `colored_print!` write to.
+pub const KERNEL_CONSOLE: usize = 0;
+
+static mut SHADOW_BUFFERS: [ScreenBuffer; CONSOLE_COUNT] =
+    [[[BLANK_SCREEN_CHAR; BUFFER_WIDTH]; BUFFER_HEIGHT]; CONSOLE_COUNT];
+
+/// The virtual consoles, exactly one of which owns the VGA text buffer at a time.
+pub struct Consoles {
+    writers: [Writer; CONSOLE_COUNT],
+    active: usize,
+    status_line: [ScreenChar; BUFFER_WIDTH],
+}
+
+impl Consoles {
+    /// Returns the writer of the given console.
+    ///
+    /// Panics if `index` is not below `CONSOLE_COUNT`.
+    pub fn console(&mut self, index: usize) -> &mut Writer {
+        &mut self.writers[index]
+    }
+
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    pub fn active_console(&mut self) -> &mut Writer {
+        &mut self.writers[self.active]
+    }
+
+    /// Shows the given console, moving the screen over from the active one.
+    pub fn switch_to(&mut self, index: usize) {
+        if index == self.active || index >= CONSOLE_COUNT {
+            return;
+        }
+        if let Some(screen) = self.writers[self.active].detach() {
+            self.writers[index].attach(screen);
+        }
+        self.active = index;
+    }
+
+    /// Replaces the text of the status bar, which is shared by all consoles.
+    pub fn set_status_line(&mut self, args: fmt::Arguments) {
+        use core::fmt::Write;
+
+        let mut writer = StatusLineWriter {
+            cells: &mut self.status_line,
+            column: 0,
+        };
+        // the writer truncates overlong lines instead of failing
+        let _ = writer.write_fmt(args);
+        for cell in &mut self.status_line[writer.column..] {
+            *cell = STATUS_BLANK_SCREEN_CHAR;
+        }
+        self.draw_status_line();
+    }
+
+    fn draw_status_line(&mut self) {
+        if let Some(screen) = &mut self.writers[self.active].screen {
+            for (col, &screen_char) in self.status_line.iter().enumerate() {
+                screen.chars[STATUS_ROW][col].write(screen_char);
+            }
+        }
+    }
+}
+
+/// Formats into the status bar cells, dropping whatever does not fit on the row.
+struct StatusLineWriter<'a> {
+    cells: &'a mut [ScreenChar; BUFFER_WIDTH],
+    column: usize,
+}
+
+impl fmt::Write for StatusLineWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for character in s.chars() {
+            if self.column >= BUFFER_WIDTH {
+                break;
+            }
+            self.cells[self.column] = ScreenChar {
+                ascii_character: cp437::encode(character),
+                color_code: STATUS_BLANK_SCREEN_CHAR.color_code,
+            };
+            self.column += 1;
+        }
+        Ok(())
+    }
+}
+
 lazy_static! {
-    pub static ref WRITER: Mutex<Writer> = Mutex::new(Writer {
-        column_position: 0,
-        color_code: color_code!(),
-        buffer: unsafe { &mut *(0xb8000 as *mut Buffer) },
-    });
+    pub static ref CONSOLES: IrqSafeMutex<Consoles> = {
+        let [kernel_shadow, shadow_1, shadow_2, shadow_3, shadow_4, shadow_5] =
+            unsafe { &mut SHADOW_BUFFERS };
+        let screen = unsafe { &mut *(0xb8000 as *mut Buffer) };
+
+        // keep whatever the bootloader left on the screen
+        for (row, line) in kernel_shadow.iter_mut().enumerate() {
+            for (col, screen_char) in line.iter_mut().enumerate() {
+                *screen_char = screen.chars[row][col].read();
+            }
+        }
+        let mut kernel_console = Writer::new(kernel_shadow);
+        kernel_console.attach(screen);
+
+        let mut consoles = Consoles {
+            writers: [
+                kernel_console,
+                Writer::new(shadow_1),
+                Writer::new(shadow_2),
+                Writer::new(shadow_3),
+                Writer::new(shadow_4),
+                Writer::new(shadow_5),
+            ],
+            active: KERNEL_CONSOLE,
+            status_line: [STATUS_BLANK_SCREEN_CHAR; BUFFER_WIDTH],
+        };
+        consoles.draw_status_line();
+        IrqSafeMutex::new(consoles)
+    };
 }
 
 #[doc(hidden)]
-pub fn _print(args: fmt::Arguments) {
+pub fn _print(color_code: Option<ColorCode>, args: fmt::Arguments) {
     use core::fmt::Write;
-    use x86_64::instructions::interrupts;
 
-    interrupts::without_interrupts(|| {
-        WRITER
-            .lock()
-            .write_fmt(args)
-            .expect("Printing to vga failed");
-    });
+    let mut consoles = CONSOLES.lock();
+    let writer = consoles.console(KERNEL_CONSOLE);
+    let original_color_code = writer.color_code;
+    writer.color_code = color_code.unwrap_or(original_color_code);
+    writer.write_fmt(args).expect("Printing to vga failed");
+    if color_code.is_some() {
+        writer.color_code = original_color_code;
+    }
+}
+
+#[doc(hidden)]
+pub fn _console_print(console: usize, args: fmt::Arguments) {
+    use core::fmt::Write;
+
+    let mut consoles = CONSOLES.lock();
+    // the framebuffer console only shows the active console, the others keep their shadow
+    if framebuffer::is_enabled() && console == consoles.active() {
+        drop(consoles);
+        return framebuffer::_print(None, args);
+    }
+    consoles
+        .console(console)
+        .write_fmt(args)
+        .expect("Printing to vga failed");
 }
 
+/// Like `print!`, but writes to the given virtual console.
 #[macro_export]
-macro_rules! print {
-    ($($arg:tt)*) => ($crate::vga_buffer::_print(format_args!($($arg)*)));
+macro_rules! console_print {
+    ($console:expr, $($arg:tt)*) => ($crate::vga_buffer::_console_print($console, format_args!($($arg)*)));
 }
 
 #[macro_export]
-macro_rules! println {
-    () => ($crate::print!("\n"));
-    ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
+macro_rules! console_println {
+    ($console:expr) => ($crate::console_print!($console, "\n"));
+    ($console:expr, $($arg:tt)*) => ($crate::console_print!($console, "{}\n", format_args!($($arg)*)));
 }
 
-#[doc(hidden)]
-pub fn _colored_print(color_code: ColorCode, args: fmt::Arguments) {
-    use core::fmt::Write;
-    use x86_64::instructions::interrupts;
+/// Runs `f` on the writer of the given console, e.g. to draw with the `Rect` based API.
+///
+/// Panics if `index` is not below `CONSOLE_COUNT`.
+pub fn with_console<F, R>(index: usize, f: F) -> R
+where
+    F: FnOnce(&mut Writer) -> R,
+{
+    f(CONSOLES.lock().console(index))
+}
 
-    interrupts::without_interrupts(|| {
-        let mut writer = WRITER.lock();
-        let original_color_code = writer.color_code;
-        writer.color_code = color_code;
-        writer.write_fmt(args).expect("Printing to vga failed");
-        writer.color_code = original_color_code;
-        drop(writer);
-    });
+/// Shows the given virtual console on the screen.
+pub fn switch_console(index: usize) {
+    CONSOLES.lock().switch_to(index);
 }
 
-#[macro_export]
-macro_rules! colored_print {
-    ($color_code:expr, $($arg:tt)*) => ($crate::vga_buffer::_colored_print($color_code, format_args!($($arg)*)));
+/// Returns the index of the virtual console that is currently shown.
+pub fn active_console() -> usize {
+    CONSOLES.lock().active()
+}
+
+/// Keeps the last `lines` rows that scroll off the screen on the heap.
+///
+/// Must be called after the heap has been initialized.
+pub fn init_scrollback(lines: usize) {
+    let mut consoles = CONSOLES.lock();
+    for index in 0..CONSOLE_COUNT {
+        consoles.console(index).enable_scrollback(lines);
+    }
+}
+
+/// Shows the formatted text in the status bar on the reserved top row.
+pub fn set_status_line(args: fmt::Arguments) {
+    if framebuffer::is_enabled() {
+        return;
+    }
+    CONSOLES.lock().set_status_line(args);
+}
+
+pub fn scroll_view_up(lines: usize) {
+    CONSOLES.lock().active_console().scroll_view_up(lines);
+}
+
+pub fn scroll_view_down(lines: usize) {
+    CONSOLES.lock().active_console().scroll_view_down(lines);
+}
+
+/// Shows or hides the blinking hardware cursor.
+pub fn set_cursor_visible(visible: bool) {
+    let mut consoles = CONSOLES.lock();
+    let writer = consoles.active_console();
+    if visible {
+        writer.show_cursor();
+    } else {
+        writer.hide_cursor();
+    }
+}
+
+pub fn set_cursor_shape(shape: CursorShape) {
+    CONSOLES.lock().active_console().set_cursor_shape(shape);
+}
+
+#[cfg(test)]
+fn kernel_console_char(row: usize, col: usize) -> ScreenChar {
+    CONSOLES.lock().console(KERNEL_CONSOLE).shadow[row][col]
 }
 
 #[test_case]
@@ -205,11 +807,7 @@ fn test_println_output() {
         println!("\n{}", s);
         s.chars().enumerate().for_each(|(i, c)| {
             assert_eq!(
-                char::from(
-                    WRITER.lock().buffer.chars[BUFFER_HEIGHT - 2][i]
-                        .read()
-                        .ascii_character
-                ),
+                char::from(kernel_console_char(BUFFER_HEIGHT - 2, i).ascii_character),
                 c
             );
         });
@@ -228,8 +826,7 @@ fn test_println_text_wrap() {
         s.chars().enumerate().for_each(|(i, c)| {
             assert_eq!(
                 char::from(
-                    WRITER.lock().buffer.chars[start_line + (i / BUFFER_WIDTH)][i % BUFFER_WIDTH]
-                        .read()
+                    kernel_console_char(start_line + (i / BUFFER_WIDTH), i % BUFFER_WIDTH)
                         .ascii_character
                 ),
                 c
@@ -249,9 +846,119 @@ fn test_colored_print_output() {
         println!();
         colored_print!(color_code, "{}", s);
         s.chars().enumerate().for_each(|(i, c)| {
-            let screen_char = WRITER.lock().buffer.chars[BUFFER_HEIGHT - 1][i].read();
+            let screen_char = kernel_console_char(BUFFER_HEIGHT - 1, i);
             assert_eq!(char::from(screen_char.ascii_character), c);
             assert_eq!(screen_char.color_code, color_code)
         });
     });
 }
+
+#[test_case]
+fn test_println_unicode() {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        println!("\né─░😭x");
+        let glyphs = [0x82, 0xc4, 0xb0, cp437::REPLACEMENT_GLYPH, b'x'];
+        for (col, &glyph) in glyphs.iter().enumerate() {
+            assert_eq!(
+                kernel_console_char(BUFFER_HEIGHT - 2, col).ascii_character,
+                glyph
+            );
+        }
+    });
+}
+
+#[test_case]
+fn test_ansi_sgr_colors() {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        println!();
+        print!("\x1b[31;44mR\x1b[1;32mG\x1b[0mD");
+        let row = BUFFER_HEIGHT - 1;
+        assert_eq!(
+            kernel_console_char(row, 0).color_code,
+            color_code!(Color::Red, Color::Blue)
+        );
+        assert_eq!(
+            kernel_console_char(row, 1).color_code,
+            color_code!(Color::LightGreen, Color::Blue)
+        );
+        assert_eq!(kernel_console_char(row, 2).color_code, color_code!());
+    });
+}
+
+#[test_case]
+fn test_sgr_params_round_trip() {
+    for value in 0..=u8::MAX {
+        let color_code = ColorCode(value);
+        let (foreground, background) = sgr_params(color_code);
+        let mut bold = false;
+        assert_eq!(
+            select_graphic_rendition(color_code!(), &mut bold, &[foreground, background]),
+            color_code
+        );
+    }
+}
+
+#[test_case]
+fn test_ansi_cursor_movement_and_erase() {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        println!();
+        print!("abcdef\x1b[3D\x1b[K\x1b[1Ax\x1b[sy\x1b[u");
+        let last_row = BUFFER_HEIGHT - 1;
+        assert_eq!(kernel_console_char(last_row, 2).ascii_character, b'c');
+        assert_eq!(kernel_console_char(last_row, 3).ascii_character, b' ');
+        let above_row = BUFFER_HEIGHT - 2;
+        assert_eq!(kernel_console_char(above_row, 3).ascii_character, b'x');
+        assert_eq!(kernel_console_char(above_row, 4).ascii_character, b'y');
+        let mut consoles = CONSOLES.lock();
+        let writer = consoles.console(KERNEL_CONSOLE);
+        assert_eq!(writer.row_position, BUFFER_HEIGHT - 2);
+        assert_eq!(writer.column_position, 4);
+    });
+    println!("\x1b[{}H", BUFFER_HEIGHT);
+}
+
+#[test_case]
+fn test_virtual_console_switch() {
+    use x86_64::instructions::interrupts;
+
+    let screen = 0xb8000 as *const ScreenChar;
+    let last_row_start = (BUFFER_HEIGHT - 1) * BUFFER_WIDTH;
+    interrupts::without_interrupts(|| {
+        println!();
+        console_print!(1, "\x1b[{}Hon console 1", BUFFER_HEIGHT);
+        let shown = unsafe { screen.add(last_row_start).read_volatile() };
+        assert_eq!(shown.ascii_character, b' ');
+
+        switch_console(1);
+        let shown = unsafe { screen.add(last_row_start).read_volatile() };
+        assert_eq!(shown.ascii_character, b'o');
+
+        switch_console(KERNEL_CONSOLE);
+        let shown = unsafe { screen.add(last_row_start).read_volatile() };
+        assert_eq!(shown.ascii_character, b' ');
+    });
+}
+
+#[test_case]
+fn test_status_line_is_not_scrolled() {
+    use x86_64::instructions::interrupts;
+
+    let screen = 0xb8000 as *const ScreenChar;
+    interrupts::without_interrupts(|| {
+        set_status_line(format_args!("status {}", 42));
+        for _ in 0..BUFFER_HEIGHT {
+            println!("scrolling line");
+        }
+        for (col, &byte) in b"status 42 ".iter().enumerate() {
+            let shown = unsafe { screen.add(STATUS_ROW * BUFFER_WIDTH + col).read_volatile() };
+            assert_eq!(shown.ascii_character, byte);
+        }
+        assert_eq!(kernel_console_char(TEXT_TOP, 0).ascii_character, b's');
+    });
+}
@@ -0,0 +1,175 @@
+const MAX_PARAMS: usize = 8;
+
+/// What the writer should do in response to a character fed into the [`Parser`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Draw the character at the cursor.
+    Print(char),
+    /// Run a C0 control character such as `\n`, `\r`, `\t` or backspace.
+    Execute(u8),
+    /// A complete control sequence (`ESC [ ... final`).
+    Csi(Csi),
+    /// `ESC 7`
+    SaveCursor,
+    /// `ESC 8`
+    RestoreCursor,
+    /// `ESC c`
+    Reset,
+}
+
+/// A parsed control sequence with up to `MAX_PARAMS` numeric parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Csi {
+    params: [u16; MAX_PARAMS],
+    len: usize,
+    private: bool,
+    final_byte: u8,
+}
+
+impl Csi {
+    const fn new() -> Self {
+        Csi {
+            params: [0; MAX_PARAMS],
+            len: 0,
+            private: false,
+            final_byte: 0,
+        }
+    }
+
+    pub fn params(&self) -> &[u16] {
+        &self.params[..self.len]
+    }
+
+    /// Returns the parameter at `index`, or `default` if it is missing or zero.
+    pub fn param(&self, index: usize, default: u16) -> u16 {
+        match self.params().get(index) {
+            Some(&0) | None => default,
+            Some(&value) => value,
+        }
+    }
+
+    /// Whether the sequence carried a `?` private marker, e.g. `ESC [ ? 25 l`.
+    pub fn is_private(&self) -> bool {
+        self.private
+    }
+
+    pub fn final_byte(&self) -> u8 {
+        self.final_byte
+    }
+
+    fn push_digit(&mut self, digit: u8) {
+        if self.len == 0 {
+            self.len = 1;
+        }
+        let param = &mut self.params[self.len - 1];
+        *param = param.saturating_mul(10).saturating_add(u16::from(digit));
+    }
+
+    fn next_param(&mut self) {
+        if self.len == 0 {
+            self.len = 1;
+        }
+        if self.len < MAX_PARAMS {
+            self.len += 1;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    Csi,
+}
+
+/// Character-at-a-time state machine for the ANSI/VT100 escape sequences we support.
+pub struct Parser {
+    state: State,
+    csi: Csi,
+}
+
+impl Parser {
+    pub const fn new() -> Self {
+        Parser {
+            state: State::Ground,
+            csi: Csi::new(),
+        }
+    }
+
+    pub fn advance(&mut self, character: char) -> Option<Action> {
+        match self.state {
+            State::Ground => match character {
+                '\x1b' => {
+                    self.state = State::Escape;
+                    None
+                }
+                '\x00'..='\x1f' | '\x7f' => Some(Action::Execute(character as u8)),
+                character => Some(Action::Print(character)),
+            },
+            State::Escape => {
+                self.state = State::Ground;
+                match character {
+                    '[' => {
+                        self.csi = Csi::new();
+                        self.state = State::Csi;
+                        None
+                    }
+                    '7' => Some(Action::SaveCursor),
+                    '8' => Some(Action::RestoreCursor),
+                    'c' => Some(Action::Reset),
+                    '\x1b' => {
+                        self.state = State::Escape;
+                        None
+                    }
+                    _ => None,
+                }
+            }
+            State::Csi => match character {
+                '0'..='9' => {
+                    self.csi.push_digit(character as u8 - b'0');
+                    None
+                }
+                ';' => {
+                    self.csi.next_param();
+                    None
+                }
+                '?' => {
+                    self.csi.private = true;
+                    None
+                }
+                // intermediate bytes are not used by any sequence we support
+                ' '..='/' => None,
+                '@'..='~' => {
+                    self.state = State::Ground;
+                    self.csi.final_byte = character as u8;
+                    Some(Action::Csi(self.csi))
+                }
+                '\x1b' => {
+                    self.state = State::Escape;
+                    None
+                }
+                _ => {
+                    self.state = State::Ground;
+                    None
+                }
+            },
+        }
+    }
+}
+
+#[test_case]
+fn test_parse_csi_params() {
+    let mut parser = Parser::new();
+    let mut actions = "\x1b[12;;3H"
+        .chars()
+        .filter_map(|character| parser.advance(character));
+    match actions.next() {
+        Some(Action::Csi(csi)) => {
+            assert_eq!(csi.params(), &[12, 0, 3]);
+            assert_eq!(csi.param(1, 1), 1);
+            assert_eq!(csi.final_byte(), b'H');
+        }
+        action => panic!("unexpected action {:?}", action),
+    }
+    assert_eq!(actions.next(), None);
+}
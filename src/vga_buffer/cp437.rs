@@ -0,0 +1,54 @@
+/// Glyph drawn for characters that code page 437 has no glyph for.
+pub const REPLACEMENT_GLYPH: u8 = 0xfe;
+
+/// Characters for the glyphs 0x01 to 0x1f, which are control codes in ASCII.
+const LOW_GLYPHS: [char; 31] = [
+    '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼', '►', '◄', '↕', '‼',
+    '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼',
+];
+
+/// Characters for the glyphs 0x80 to 0xff.
+const HIGH_GLYPHS: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', //
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', //
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', //
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐', //
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧', //
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀', //
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', //
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}', //
+];
+
+/// Maps a character to its code page 437 glyph, or [`REPLACEMENT_GLYPH`] if there is none.
+pub fn encode(character: char) -> u8 {
+    match character {
+        ' '..='~' => character as u8,
+        '⌂' => 0x7f,
+        // look-alikes that are commonly used in place of the glyph's own character
+        'β' => 0xe1,
+        'μ' => 0xe6,
+        'Ω' => 0xea,
+        '∑' => 0xe4,
+        '∈' => 0xee,
+        _ => {
+            if let Some(index) = LOW_GLYPHS.iter().position(|&glyph| glyph == character) {
+                index as u8 + 0x01
+            } else if let Some(index) = HIGH_GLYPHS.iter().position(|&glyph| glyph == character) {
+                index as u8 + 0x80
+            } else {
+                REPLACEMENT_GLYPH
+            }
+        }
+    }
+}
+
+#[test_case]
+fn test_encode() {
+    assert_eq!(encode('A'), b'A');
+    assert_eq!(encode('é'), 0x82);
+    assert_eq!(encode('╬'), 0xce);
+    assert_eq!(encode('▓'), 0xb2);
+    assert_eq!(encode('π'), 0xe3);
+    assert_eq!(encode('☺'), 0x01);
+    assert_eq!(encode('😭'), REPLACEMENT_GLYPH);
+}
@@ -0,0 +1,79 @@
+use x86_64::instructions::port::Port;
+
+const CRTC_INDEX_PORT: u16 = 0x3d4;
+const CRTC_DATA_PORT: u16 = 0x3d5;
+
+const CURSOR_START_REGISTER: u8 = 0x0a;
+const CURSOR_END_REGISTER: u8 = 0x0b;
+const CURSOR_LOCATION_HIGH_REGISTER: u8 = 0x0e;
+const CURSOR_LOCATION_LOW_REGISTER: u8 = 0x0f;
+
+const CURSOR_DISABLE: u8 = 1 << 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    Underline,
+    Block,
+}
+
+impl CursorShape {
+    /// First and last scanline of the 16 scanline high character cell.
+    fn scanlines(self) -> (u8, u8) {
+        match self {
+            CursorShape::Underline => (13, 14),
+            CursorShape::Block => (0, 15),
+        }
+    }
+}
+
+fn read_register(index: u8) -> u8 {
+    let mut index_port = Port::new(CRTC_INDEX_PORT);
+    let mut data_port = Port::new(CRTC_DATA_PORT);
+    unsafe {
+        index_port.write(index);
+        data_port.read()
+    }
+}
+
+fn write_register(index: u8, value: u8) {
+    let mut index_port = Port::new(CRTC_INDEX_PORT);
+    let mut data_port = Port::new(CRTC_DATA_PORT);
+    unsafe {
+        index_port.write(index);
+        data_port.write(value);
+    }
+}
+
+pub(super) fn set_position(row: usize, col: usize, width: usize) {
+    let position = (row * width + col) as u16;
+    write_register(CURSOR_LOCATION_HIGH_REGISTER, (position >> 8) as u8);
+    write_register(CURSOR_LOCATION_LOW_REGISTER, position as u8);
+}
+
+/// Enables the cursor with the given shape, keeping the reserved register bits intact.
+pub(super) fn enable(shape: CursorShape) {
+    let (start, end) = shape.scanlines();
+    let start_register = read_register(CURSOR_START_REGISTER) & 0xc0;
+    write_register(CURSOR_START_REGISTER, start_register | start);
+    let end_register = read_register(CURSOR_END_REGISTER) & 0xe0;
+    write_register(CURSOR_END_REGISTER, end_register | end);
+}
+
+pub(super) fn disable() {
+    write_register(CURSOR_START_REGISTER, CURSOR_DISABLE);
+}
+
+#[test_case]
+fn test_cursor_follows_output() {
+    use super::{BUFFER_HEIGHT, BUFFER_WIDTH};
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        crate::println!();
+        crate::print!("abc");
+        let high = read_register(CURSOR_LOCATION_HIGH_REGISTER);
+        let low = read_register(CURSOR_LOCATION_LOW_REGISTER);
+        let position = usize::from(u16::from_le_bytes([low, high]));
+        assert_eq!(position, (BUFFER_HEIGHT - 1) * BUFFER_WIDTH + 3);
+    });
+}
@@ -0,0 +1,39 @@
+use super::{ScreenChar, BUFFER_WIDTH};
+use alloc::collections::VecDeque;
+
+pub(super) type Row = [ScreenChar; BUFFER_WIDTH];
+
+/// Ring of the rows that scrolled off the top of the screen, oldest first.
+///
+/// The ring only grows as rows are pushed, so consoles that never scroll cost nothing.
+pub(super) struct Scrollback {
+    lines: VecDeque<Row>,
+    capacity: usize,
+}
+
+impl Scrollback {
+    pub fn new(capacity: usize) -> Self {
+        Scrollback {
+            lines: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, row: Row) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(row);
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn line(&self, index: usize) -> &Row {
+        &self.lines[index]
+    }
+}
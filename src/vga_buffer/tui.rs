@@ -0,0 +1,193 @@
+use super::{cp437, ColorCode, ScreenChar, Writer, BUFFER_WIDTH, TEXT_HEIGHT, TEXT_TOP};
+
+/// A rectangle of cells, in rows and columns of a console's text area.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub row: usize,
+    pub col: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub const fn new(row: usize, col: usize, width: usize, height: usize) -> Rect {
+        Rect {
+            row,
+            col,
+            width,
+            height,
+        }
+    }
+
+    /// The whole text area of a console.
+    pub const fn screen() -> Rect {
+        Rect::new(0, 0, BUFFER_WIDTH, TEXT_HEIGHT)
+    }
+
+    /// Returns the part of the rectangle that lies on the screen.
+    fn clipped(self) -> Rect {
+        let row = self.row.min(TEXT_HEIGHT);
+        let col = self.col.min(BUFFER_WIDTH);
+        Rect {
+            row,
+            col,
+            width: self.width.min(BUFFER_WIDTH - col),
+            height: self.height.min(TEXT_HEIGHT - row),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoxStyle {
+    Single,
+    Double,
+}
+
+impl BoxStyle {
+    /// CP437 glyphs for the top left, top right, bottom left and bottom right
+    /// corners, followed by the horizontal and the vertical line.
+    fn glyphs(self) -> [u8; 6] {
+        match self {
+            BoxStyle::Single => [0xda, 0xbf, 0xc0, 0xd9, 0xc4, 0xb3],
+            BoxStyle::Double => [0xc9, 0xbb, 0xc8, 0xbc, 0xcd, 0xba],
+        }
+    }
+}
+
+/// Drawing at fixed positions, for full-screen programs.
+///
+/// Rows and columns count from the top left cell of the console's text area;
+/// anything outside of it is clipped. None of these move the write position.
+impl Writer {
+    /// Writes `s` starting at the given cell, cutting it off at the end of the row.
+    pub fn write_str_at(&mut self, row: usize, col: usize, s: &str, color_code: ColorCode) {
+        self.reset_view();
+        if row >= TEXT_HEIGHT {
+            return;
+        }
+        for (col, character) in (col..BUFFER_WIDTH).zip(s.chars()) {
+            self.put(row, col, cp437::encode(character), color_code);
+        }
+    }
+
+    /// Fills the rectangle with `character`.
+    pub fn fill_rect(&mut self, rect: Rect, character: char, color_code: ColorCode) {
+        self.reset_view();
+        let rect = rect.clipped();
+        let glyph = cp437::encode(character);
+        for row in rect.row..rect.row + rect.height {
+            for col in rect.col..rect.col + rect.width {
+                self.put(row, col, glyph, color_code);
+            }
+        }
+    }
+
+    /// Draws the outline of the rectangle with line drawing characters.
+    pub fn draw_box(&mut self, rect: Rect, style: BoxStyle, color_code: ColorCode) {
+        if rect.width < 2 || rect.height < 2 {
+            return;
+        }
+        self.reset_view();
+        let [top_left, top_right, bottom_left, bottom_right, horizontal, vertical] = style.glyphs();
+        let right = rect.col + rect.width - 1;
+        let bottom = rect.row + rect.height - 1;
+
+        for col in rect.col + 1..right {
+            self.put(rect.row, col, horizontal, color_code);
+            self.put(bottom, col, horizontal, color_code);
+        }
+        for row in rect.row + 1..bottom {
+            self.put(row, rect.col, vertical, color_code);
+            self.put(row, right, vertical, color_code);
+        }
+        self.put(rect.row, rect.col, top_left, color_code);
+        self.put(rect.row, right, top_right, color_code);
+        self.put(bottom, rect.col, bottom_left, color_code);
+        self.put(bottom, right, bottom_right, color_code);
+    }
+
+    /// Blanks the rectangle with the console's current colors.
+    pub fn clear_rect(&mut self, rect: Rect) {
+        let color_code = self.color_code;
+        self.fill_rect(rect, ' ', color_code);
+    }
+
+    /// Blanks the text area and moves the write position to its top left cell.
+    pub fn clear_screen(&mut self) {
+        self.clear_rect(Rect::screen());
+        self.row_position = TEXT_TOP;
+        self.column_position = 0;
+        self.update_cursor();
+    }
+
+    /// Returns the cell at the given position, or `None` if it is off the screen.
+    pub fn cell(&self, row: usize, col: usize) -> Option<ScreenChar> {
+        if row >= TEXT_HEIGHT || col >= BUFFER_WIDTH {
+            return None;
+        }
+        Some(self.shadow[TEXT_TOP + row][col])
+    }
+
+    fn put(&mut self, row: usize, col: usize, glyph: u8, color_code: ColorCode) {
+        if row < TEXT_HEIGHT && col < BUFFER_WIDTH {
+            self.write_cell(
+                TEXT_TOP + row,
+                col,
+                ScreenChar {
+                    ascii_character: glyph,
+                    color_code,
+                },
+            );
+        }
+    }
+}
+
+#[test_case]
+fn test_draw_box_and_text() {
+    use super::{with_console, Color};
+    use crate::color_code;
+
+    let color_code = color_code!(Color::Yellow, Color::Blue);
+    with_console(2, |writer| {
+        writer.clear_screen();
+        writer.draw_box(Rect::new(1, 1, 6, 3), BoxStyle::Single, color_code);
+        writer.write_str_at(2, 2, "hi─", color_code);
+
+        let glyph = |row, col| writer.cell(row, col).unwrap().ascii_character;
+        assert_eq!(glyph(1, 1), 0xda);
+        assert_eq!(glyph(1, 3), 0xc4);
+        assert_eq!(glyph(2, 6), 0xb3);
+        assert_eq!(glyph(3, 6), 0xd9);
+        assert_eq!(glyph(2, 2), b'h');
+        assert_eq!(glyph(2, 4), 0xc4);
+        assert_eq!(glyph(0, 0), b' ');
+        assert_eq!(writer.cell(2, 3).unwrap().color_code, color_code);
+        assert_eq!(writer.cell(TEXT_HEIGHT, 0), None);
+    });
+}
+
+#[test_case]
+fn test_fill_rect_is_clipped() {
+    use super::{with_console, Color};
+    use crate::color_code;
+
+    let color_code = color_code!(Color::Black, Color::Green);
+    with_console(2, |writer| {
+        writer.clear_screen();
+        writer.fill_rect(
+            Rect::new(TEXT_HEIGHT - 1, BUFFER_WIDTH - 2, 10, 10),
+            '#',
+            color_code,
+        );
+        let cell = writer.cell(TEXT_HEIGHT - 1, BUFFER_WIDTH - 1).unwrap();
+        assert_eq!(cell.ascii_character, b'#');
+        assert_eq!(cell.color_code, color_code);
+        assert_eq!(
+            writer
+                .cell(TEXT_HEIGHT - 2, BUFFER_WIDTH - 1)
+                .unwrap()
+                .ascii_character,
+            b' '
+        );
+    });
+}
@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(dv_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use bootloader::{entry_point, BootInfo};
+use core::panic::PanicInfo;
+use dv_os::acpi;
+
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    use dv_os::allocator;
+    use dv_os::memory::{self, BootInfoFrameAllocator};
+    use x86_64::VirtAddr;
+
+    dv_os::init();
+    let phys_mem_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe { memory::init(phys_mem_offset) };
+    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+    allocator::init_heap(&mut mapper, &mut frame_allocator).expect("heap initialization failed");
+    acpi::init().expect("ACPI tables not found");
+
+    test_main();
+    loop {}
+}
+
+#[test_case]
+fn madt_lists_the_boot_processor_and_an_io_apic() {
+    let madt = acpi::madt().unwrap();
+    assert!(madt.processors.iter().any(|cpu| cpu.enabled));
+    assert!(!madt.io_apics.is_empty());
+    // QEMU wires the PIT to the second I/O APIC input
+    assert_eq!(madt.isa_irq(0).gsi, 2);
+}
+
+#[test_case]
+fn fadt_has_pm1_control_block() {
+    let fadt = acpi::fadt().unwrap();
+    assert!(fadt.pm1a_control_block.is_some());
+    assert!(fadt.dsdt.as_u64() != 0);
+    assert_eq!(acpi::find_table(b"FACP").unwrap().signature(), *b"FACP");
+}
+
+#[test_case]
+fn missing_tables_are_reported() {
+    assert_eq!(
+        acpi::find_table(b"NONE").err(),
+        Some(acpi::AcpiError::TableNotFound(*b"NONE"))
+    );
+    assert_eq!(acpi::init(), Err(acpi::AcpiError::AlreadyInitialized));
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    dv_os::test_panic_handler(info)
+}
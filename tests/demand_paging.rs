@@ -0,0 +1,71 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(dv_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use bootloader::{entry_point, BootInfo};
+use core::panic::PanicInfo;
+use dv_os::memory::{self, RegionError};
+use x86_64::{structures::paging::PageTableFlags, VirtAddr};
+
+entry_point!(main);
+
+const LAZY_START: u64 = 0x_5555_0000_0000;
+const LAZY_SIZE: u64 = 16 * 4096;
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    use dv_os::allocator;
+    use dv_os::memory::BootInfoFrameAllocator;
+
+    dv_os::init();
+    let phys_mem_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe { memory::init(phys_mem_offset) };
+    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+    allocator::init_heap(&mut mapper, &mut frame_allocator).expect("heap initialization failed");
+    memory::init_demand_paging(mapper, frame_allocator);
+    memory::register_lazy_region(
+        VirtAddr::new(LAZY_START),
+        LAZY_SIZE,
+        PageTableFlags::WRITABLE,
+    )
+    .expect("lazy region not registered");
+
+    test_main();
+    loop {}
+}
+
+#[test_case]
+fn pages_are_mapped_on_first_access() {
+    let second_page = VirtAddr::new(LAZY_START + 4096);
+    assert_eq!(memory::page_flags(second_page), None);
+
+    let values = second_page.as_mut_ptr::<u64>();
+    unsafe {
+        assert_eq!(values.read_volatile(), 0);
+        values.add(7).write_volatile(42);
+        assert_eq!(values.add(7).read_volatile(), 42);
+    }
+    assert!(memory::page_flags(second_page)
+        .unwrap()
+        .contains(PageTableFlags::WRITABLE));
+    assert_eq!(memory::page_flags(VirtAddr::new(LAZY_START)), None);
+}
+
+#[test_case]
+fn overlapping_and_unaligned_regions_are_rejected() {
+    let flags = PageTableFlags::WRITABLE;
+    assert_eq!(
+        memory::register_lazy_region(VirtAddr::new(LAZY_START + LAZY_SIZE - 4096), 8192, flags),
+        Err(RegionError::Overlaps)
+    );
+    assert_eq!(
+        memory::register_lazy_region(VirtAddr::new(LAZY_START + LAZY_SIZE + 1), 4096, flags),
+        Err(RegionError::Unaligned)
+    );
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    dv_os::test_panic_handler(info)
+}
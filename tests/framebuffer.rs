@@ -0,0 +1,61 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(dv_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use bootloader::{entry_point, BootInfo};
+use core::panic::PanicInfo;
+use dv_os::framebuffer::{self, font, Rgb};
+use dv_os::{color_code, colored_print, print, Color};
+
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    use dv_os::memory::{self, BootInfoFrameAllocator};
+    use x86_64::VirtAddr;
+
+    dv_os::init();
+    let phys_mem_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe { memory::init(phys_mem_offset) };
+    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+    framebuffer::init(1024, 768, &mut mapper, &mut frame_allocator)
+        .expect("framebuffer initialization failed");
+
+    test_main();
+    loop {}
+}
+
+fn pixel(x: usize, y: usize) -> Rgb {
+    framebuffer::with_framebuffer(|fb| fb.pixel(x, y))
+        .flatten()
+        .expect("framebuffer not enabled")
+}
+
+#[test_case]
+fn print_draws_glyphs() {
+    print!("\x1b[2J");
+    colored_print!(color_code!(Color::Yellow, Color::Blue), "A");
+    print!("B");
+
+    // row 2 of the 'A' glyph is 0b0001_1000
+    let row = font::glyph('A')[2];
+    assert_eq!(row, 0x18);
+    assert_eq!(pixel(3, 2), Rgb::from(Color::Yellow));
+    assert_eq!(pixel(0, 2), Rgb::from(Color::Blue));
+    assert_eq!(pixel(font::GLYPH_WIDTH, 0), Rgb::from(Color::Black));
+}
+
+#[test_case]
+fn draw_line() {
+    let color = Rgb::new(0x12, 0x34, 0x56);
+    framebuffer::with_framebuffer(|fb| fb.draw_line((100, 100), (200, 150), color));
+    assert_eq!(pixel(100, 100), color);
+    assert_eq!(pixel(200, 150), color);
+    assert_eq!(pixel(150, 125), color);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    dv_os::test_panic_handler(info)
+}
@@ -0,0 +1,55 @@
+#![no_std]
+#![no_main]
+#![feature(asm)]
+
+use core::{fmt::Write, panic::PanicInfo};
+use dv_os::{exit_qemu, serial_print, serial_println, QemuExitCode};
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    serial_print!("invalid_opcode::invalid_opcode...\t");
+
+    dv_os::init();
+    unsafe { asm!("ud2") };
+
+    serial_println!("[test did not panic]");
+    exit_qemu(QemuExitCode::Failed);
+    dv_os::hlt_loop();
+}
+
+/// Keeps the start of the panic message, to check which exception it came from.
+struct Message {
+    bytes: [u8; 128],
+    len: usize,
+}
+
+impl Write for Message {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for &byte in s.as_bytes() {
+            if self.len < self.bytes.len() {
+                self.bytes[self.len] = byte;
+                self.len += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    let mut message = Message {
+        bytes: [0; 128],
+        len: 0,
+    };
+    let _ = write!(message, "{}", info);
+    let message = &message.bytes[..message.len];
+    if message.windows(3).any(|window| window == b"#UD") {
+        serial_println!("[ok]");
+        exit_qemu(QemuExitCode::Success);
+    } else {
+        serial_println!("[failed]\n");
+        serial_println!("Error: {}\n", info);
+        exit_qemu(QemuExitCode::Failed);
+    }
+    dv_os::hlt_loop();
+}
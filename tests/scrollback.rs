@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(dv_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use bootloader::{entry_point, BootInfo};
+use core::panic::PanicInfo;
+use dv_os::{println, vga_buffer, vga_buffer::BUFFER_HEIGHT, vga_buffer::BUFFER_WIDTH};
+
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    use dv_os::allocator;
+    use dv_os::memory::{self, BootInfoFrameAllocator};
+    use x86_64::VirtAddr;
+
+    dv_os::init();
+    let phys_mem_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe { memory::init(phys_mem_offset) };
+    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+    allocator::init_heap(&mut mapper, &mut frame_allocator).expect("heap initialization failed");
+    vga_buffer::init_scrollback(50);
+
+    test_main();
+    loop {}
+}
+
+fn screen_char(row: usize, col: usize) -> u8 {
+    let vga_memory = 0xb8000 as *const u16;
+    let cell = unsafe { vga_memory.add(row * BUFFER_WIDTH + col).read_volatile() };
+    cell as u8
+}
+
+#[test_case]
+fn scroll_back_and_snap_to_live() {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        println!("first line of history");
+        for _ in 0..BUFFER_HEIGHT {
+            println!();
+        }
+        println!("live line");
+        assert_eq!(screen_char(BUFFER_HEIGHT - 2, 0), b'l');
+
+        vga_buffer::scroll_view_up(BUFFER_HEIGHT);
+        assert_eq!(screen_char(BUFFER_HEIGHT - 3, 0), b'f');
+
+        println!();
+        assert_eq!(screen_char(BUFFER_HEIGHT - 3, 0), b'l');
+    });
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    dv_os::test_panic_handler(info)
+}
@@ -0,0 +1,83 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(dv_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use bootloader::{entry_point, BootInfo};
+use core::{
+    future::Future,
+    panic::PanicInfo,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use dv_os::{task, time::Instant};
+use futures_util::{future, stream::StreamExt, task::noop_waker};
+
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    use dv_os::allocator;
+    use dv_os::memory::{self, BootInfoFrameAllocator};
+    use x86_64::VirtAddr;
+
+    dv_os::init();
+    let phys_mem_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe { memory::init(phys_mem_offset) };
+    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+    allocator::init_heap(&mut mapper, &mut frame_allocator).expect("heap initialization failed");
+
+    test_main();
+    loop {}
+}
+
+/// Polls `future` after every interrupt until it completes.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = future;
+    let waker = noop_waker();
+    let mut context = Context::from_waker(&waker);
+    loop {
+        let pinned = unsafe { Pin::new_unchecked(&mut future) };
+        if let Poll::Ready(output) = pinned.poll(&mut context) {
+            return output;
+        }
+        x86_64::instructions::hlt();
+    }
+}
+
+#[test_case]
+fn sleep_waits_for_the_duration() {
+    let start = Instant::now();
+    block_on(task::sleep(Duration::from_millis(30)));
+    assert!(start.elapsed() >= Duration::from_millis(30));
+}
+
+#[test_case]
+fn timeout_gives_up_on_pending_future() {
+    let result = block_on(task::timeout(
+        Duration::from_millis(20),
+        future::pending::<()>(),
+    ));
+    assert_eq!(result, Err(task::Elapsed));
+}
+
+#[test_case]
+fn timeout_passes_output_through() {
+    let result = block_on(task::timeout(Duration::from_secs(1), async { 7 }));
+    assert_eq!(result, Ok(7));
+}
+
+#[test_case]
+fn interval_ticks_once_per_period() {
+    let period = Duration::from_millis(20);
+    let mut ticks = task::interval(period);
+    let first = block_on(ticks.next()).unwrap();
+    let second = block_on(ticks.next()).unwrap();
+    assert_eq!(second - first, period);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    dv_os::test_panic_handler(info)
+}
@@ -0,0 +1,133 @@
+#!/usr/bin/env python3
+"""Host side of the dvOS transfer protocol (see src/transfer.rs).
+
+Talks to the kernel's COM3 through a QEMU chardev, e.g.
+`-chardev socket,id=xfer,host=localhost,port=4322,server,nowait -device isa-serial,chardev=xfer`.
+
+    dvxfer.py status
+    dvxfer.py put NAME FILE
+    dvxfer.py get NAME FILE
+    dvxfer.py run COMMAND...
+"""
+
+import argparse
+import random
+import socket
+import struct
+import sys
+import zlib
+
+SYNC = 0x7E
+PUT_BLOB, GET_BLOB, RUN_COMMAND, STATUS = 0x01, 0x02, 0x03, 0x04
+RESPONSE, ERROR, NAK = 0x80, 0xFE, 0xFF
+MAX_PAYLOAD = 1024
+CHUNK = 512
+
+
+class Connection:
+    def __init__(self, host, port, timeout, retries):
+        self.sock = socket.create_connection((host, port))
+        self.sock.settimeout(timeout)
+        self.retries = retries
+        # a fresh start so the kernel doesn't take the first request for a repeat
+        self.seq = random.randrange(256)
+
+    def _read(self, count):
+        data = b""
+        while len(data) < count:
+            chunk = self.sock.recv(count - len(data))
+            if not chunk:
+                raise ConnectionError("connection closed")
+            data += chunk
+        return data
+
+    def _send_frame(self, seq, kind, payload):
+        header = struct.pack("<BBH", seq, kind, len(payload))
+        crc = zlib.crc32(header + payload)
+        self.sock.sendall(bytes([SYNC]) + header + payload + struct.pack("<I", crc))
+
+    def _receive_frame(self):
+        while self._read(1)[0] != SYNC:
+            pass
+        header = self._read(4)
+        seq, kind, length = struct.unpack("<BBH", header)
+        if length > MAX_PAYLOAD:
+            return None
+        payload = self._read(length)
+        (crc,) = struct.unpack("<I", self._read(4))
+        if crc != zlib.crc32(header + payload):
+            return None
+        return seq, kind, payload
+
+    def request(self, kind, payload=b""):
+        self.seq = (self.seq + 1) % 256
+        for _ in range(self.retries):
+            # resending with the same sequence number is safe, as the kernel
+            # answers repeats from its cache
+            self._send_frame(self.seq, kind, payload)
+            try:
+                frame = self._receive_frame()
+            except socket.timeout:
+                continue
+            if frame is None or frame[1] == NAK or frame[0] != self.seq:
+                continue
+            _, response_kind, response = frame
+            if response_kind == ERROR:
+                raise RuntimeError(response.decode(errors="replace"))
+            return response
+        raise TimeoutError("no valid response after %d attempts" % self.retries)
+
+
+def name_field(name):
+    encoded = name.encode()
+    return bytes([len(encoded)]) + encoded
+
+
+def put(conn, name, data):
+    offset = 0
+    while True:
+        chunk = data[offset : offset + CHUNK]
+        payload = name_field(name) + struct.pack("<I", offset) + chunk
+        conn.request(PUT_BLOB, payload)
+        offset += len(chunk)
+        if offset >= len(data):
+            return
+
+
+def get(conn, name):
+    data = b""
+    while True:
+        payload = name_field(name) + struct.pack("<IH", len(data), CHUNK)
+        chunk = conn.request(GET_BLOB, payload)
+        data += chunk
+        if len(chunk) < CHUNK:
+            return data
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument("--host", default="localhost")
+    parser.add_argument("--port", type=int, default=4322)
+    parser.add_argument("--timeout", type=float, default=1.0)
+    parser.add_argument("--retries", type=int, default=5)
+    parser.add_argument("command", choices=["status", "put", "get", "run"])
+    parser.add_argument("args", nargs="*")
+    args = parser.parse_args()
+
+    conn = Connection(args.host, args.port, args.timeout, args.retries)
+    if args.command == "status":
+        sys.stdout.write(conn.request(STATUS).decode())
+    elif args.command == "put":
+        name, path = args.args
+        with open(path, "rb") as file:
+            put(conn, name, file.read())
+    elif args.command == "get":
+        name, path = args.args
+        with open(path, "wb") as file:
+            file.write(get(conn, name))
+    else:
+        sys.stdout.write(conn.request(RUN_COMMAND, " ".join(args.args).encode()).decode())
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,126 @@
+#!/usr/bin/env python3
+"""Writes the function symbols of a dvOS kernel into its .ksyms section.
+
+The kernel resolves backtrace addresses against that table (see src/backtrace.rs).
+Patching in place moves nothing, so the addresses stay valid.
+
+    ksyms.py KERNEL
+    ksyms.py --run KERNEL [ARGS...]    also runs `bootimage runner`, for cargo
+"""
+
+import os
+import re
+import struct
+import sys
+
+MAGIC = b"KSYM"
+SECTION = ".ksyms"
+SHT_SYMTAB = 2
+STT_FUNC = 2
+MAX_NAME = 255
+
+ESCAPES = {
+    "$SP$": "@", "$BP$": "*", "$RF$": "&", "$LT$": "<", "$GT$": ">",
+    "$LP$": "(", "$RP$": ")", "$C$": ",", "$u7e$": "~", "$u20$": " ",
+    "$u27$": "'", "$u5b$": "[", "$u5d$": "]", "$u7b$": "{", "$u7d$": "}",
+    "$u3b$": ";", "$u2b$": "+", "$u22$": '"',
+}
+
+
+def demangle(name):
+    """Demangles a legacy Rust symbol, without its hash; anything else is kept."""
+    if not name.startswith("_ZN") or not name.endswith("E"):
+        return name
+    parts, rest = [], name[3:-1]
+    while rest and rest[0].isdigit():
+        digits = re.match(r"\d+", rest).group()
+        length = int(digits)
+        parts.append(rest[len(digits):len(digits) + length])
+        rest = rest[len(digits) + length:]
+    if rest or not parts:
+        return name
+    if re.fullmatch(r"h[0-9a-f]{16}", parts[-1]):
+        parts.pop()
+    # identifiers can't start with an escape, so those get an underscore in front
+    path = "::".join(part[1:] if part.startswith("_$") else part for part in parts)
+    for escape, char in ESCAPES.items():
+        path = path.replace(escape, char)
+    return path.replace("..", "::")
+
+
+def sections(elf):
+    if elf[:4] != b"\x7fELF" or elf[4] != 2 or elf[5] != 1:
+        sys.exit("ksyms: not a little endian ELF64 file")
+    shoff, = struct.unpack_from("<Q", elf, 0x28)
+    shentsize, shnum, shstrndx = struct.unpack_from("<HHH", elf, 0x3A)
+    headers = [struct.unpack_from("<IIQQQQIIQQ", elf, shoff + i * shentsize) for i in range(shnum)]
+    names = headers[shstrndx]
+    result = []
+    for header in headers:
+        start = names[4] + header[0]
+        name = elf[start:elf.index(b"\0", start)].decode()
+        result.append((name, header))
+    return result
+
+
+def functions(elf, all_sections):
+    symtab = next((h for _, h in all_sections if h[1] == SHT_SYMTAB), None)
+    if symtab is None:
+        sys.exit("ksyms: the kernel has no symbol table; is it stripped?")
+    strtab = all_sections[symtab[6]][1]
+    symbols = {}
+    for offset in range(symtab[4], symtab[4] + symtab[5], symtab[9]):
+        name, info, _, _, value, size = struct.unpack_from("<IBBHQQ", elf, offset)
+        if info & 0xF != STT_FUNC or value == 0:
+            continue
+        start = strtab[4] + name
+        name = elf[start:elf.index(b"\0", start)].decode(errors="replace")
+        symbols.setdefault(value, (size, demangle(name)))
+    return sorted((address, size, name) for address, (size, name) in symbols.items())
+
+
+def build_table(symbols):
+    strings, offsets = bytearray(), {}
+    for _, _, name in symbols:
+        if name not in offsets:
+            encoded = name.encode()[:MAX_NAME]
+            offsets[name] = len(strings)
+            strings += bytes([len(encoded)]) + encoded
+    header_size, entry_size = 12, 16
+    table = bytearray(MAGIC + struct.pack("<II", len(symbols), header_size + entry_size * len(symbols)))
+    for address, size, name in symbols:
+        table += struct.pack("<QII", address, min(size, 0xFFFFFFFF), offsets[name])
+    return bytes(table + strings)
+
+
+def patch(path):
+    with open(path, "rb") as file:
+        elf = bytearray(file.read())
+    all_sections = sections(elf)
+    section = next((h for name, h in all_sections if name == SECTION), None)
+    if section is None:
+        # binaries that never print a backtrace leave the section out
+        return
+    table = build_table(functions(elf, all_sections))
+    offset, size = section[4], section[5]
+    if len(table) > size:
+        sys.exit("ksyms: the symbol table needs {} bytes, raise SYMBOLS_SIZE from {}".format(len(table), size))
+    elf[offset:offset + size] = table + bytes(size - len(table))
+    with open(path, "wb") as file:
+        file.write(elf)
+
+
+def main():
+    args = sys.argv[1:]
+    run = args[:1] == ["--run"]
+    if run:
+        args = args[1:]
+    if not args:
+        sys.exit(__doc__)
+    patch(args[0])
+    if run:
+        os.execvp("bootimage", ["bootimage", "runner"] + args)
+
+
+if __name__ == "__main__":
+    main()